@@ -0,0 +1,252 @@
+mod tests;
+
+use std::{ops::Range, sync::Arc};
+
+use m3u8_rs::MediaPlaylist;
+
+use crate::{
+    check_indices, AimdConfig, HttpClient, SegmentCache, SegmentCachePolicy, SegmentCheck,
+};
+
+pub struct ValidRangesResponse {
+    pub playlist: MediaPlaylist,
+    /// Indices into the checked playlist of the runs of valid segments.
+    pub ranges: Vec<Range<usize>>,
+    pub num_checked: usize,
+    /// Set if checking stopped early. `playlist` and `ranges` are then only a guess.
+    pub error: Option<anyhow::Error>,
+}
+
+enum Stage {
+    /// Check every `stride` segments.
+    Grid,
+    /// Sort the regions between the grid checks.
+    Classify,
+    /// Check the middle of every region that contains a boundary.
+    Bisect,
+    /// Halve the regions that contain a boundary.
+    Split,
+    /// Check every segment of the ambiguous regions.
+    Full,
+    Done,
+}
+
+/// Decides which segments to check next to find the runs of valid segments.
+///
+/// Segments are first checked every `stride` segments. Between two neighbouring checks that agree,
+/// every segment is assumed to be the same. Between two that disagree, the boundary is found by
+/// bisection. A region with a check that could not be trusted, e.g. a timeout, is checked fully.
+pub(crate) struct RangeSearch {
+    stride: usize,
+    checks: Vec<Option<SegmentCheck>>,
+    stage: Stage,
+    /// Regions `(start, end)` whose ends disagree and that still contain a boundary.
+    splitting: Vec<(usize, usize)>,
+    /// Regions `(start, end)` that need every segment checked.
+    ambiguous: Vec<(usize, usize)>,
+}
+
+impl RangeSearch {
+    pub(crate) fn new(len: usize, stride: usize) -> Self {
+        Self {
+            stride: stride.max(1),
+            checks: vec![None; len],
+            stage: Stage::Grid,
+            splitting: vec![],
+            ambiguous: vec![],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.checks.len()
+    }
+
+    fn grid(&self) -> Vec<usize> {
+        let mut grid = (0..self.len()).step_by(self.stride).collect::<Vec<_>>();
+        if self.len() > 0 && grid.last() != Some(&(self.len() - 1)) {
+            grid.push(self.len() - 1);
+        }
+        grid
+    }
+
+    fn is_definitive(&self, index: usize) -> bool {
+        self.checks[index].is_some_and(|check| check.is_definitive())
+    }
+
+    fn is_valid(&self, index: usize) -> bool {
+        self.checks[index].is_some_and(|check| check.is_valid())
+    }
+
+    /// Sorts a region that was just checked at both ends.
+    fn classify(&mut self, start: usize, end: usize) {
+        if !self.is_definitive(start) || !self.is_definitive(end) {
+            self.ambiguous.push((start, end));
+        } else if self.is_valid(start) != self.is_valid(end) && end - start > 1 {
+            self.splitting.push((start, end));
+        }
+    }
+
+    /// Returns the indices to check next, or nothing once the search is done.
+    pub(crate) fn next_batch(&mut self) -> Vec<usize> {
+        loop {
+            match self.stage {
+                Stage::Grid => {
+                    self.stage = Stage::Classify;
+                    let grid = self.grid();
+                    if !grid.is_empty() {
+                        return grid;
+                    }
+                }
+                Stage::Classify => {
+                    for pair in self.grid().windows(2) {
+                        self.classify(pair[0], pair[1]);
+                    }
+                    self.stage = Stage::Bisect;
+                }
+                Stage::Bisect => {
+                    if self.splitting.is_empty() {
+                        self.stage = Stage::Full;
+                    } else {
+                        self.stage = Stage::Split;
+                        return self
+                            .splitting
+                            .iter()
+                            .map(|(start, end)| (start + end) / 2)
+                            .collect();
+                    }
+                }
+                Stage::Split => {
+                    for (start, end) in std::mem::take(&mut self.splitting) {
+                        let middle = (start + end) / 2;
+                        if !self.is_definitive(middle) {
+                            // the boundary could be on either side
+                            self.ambiguous.push((start, middle));
+                            self.ambiguous.push((middle, end));
+                        } else if self.is_valid(middle) == self.is_valid(start) {
+                            self.classify(middle, end);
+                        } else {
+                            self.classify(start, middle);
+                        }
+                    }
+                    self.stage = Stage::Bisect;
+                }
+                Stage::Full => {
+                    self.stage = Stage::Done;
+                    let mut indices = self
+                        .ambiguous
+                        .iter()
+                        .flat_map(|(start, end)| *start..=*end)
+                        .filter(|index| self.checks[*index].is_none())
+                        .collect::<Vec<_>>();
+                    indices.sort_unstable();
+                    indices.dedup();
+                    if !indices.is_empty() {
+                        return indices;
+                    }
+                }
+                Stage::Done => return vec![],
+            }
+        }
+    }
+
+    pub(crate) fn record(&mut self, checks: impl IntoIterator<Item = (usize, SegmentCheck)>) {
+        for (index, check) in checks {
+            self.checks[index] = Some(check);
+        }
+    }
+
+    /// Every unchecked segment is taken to be like the closest checked segment before it.
+    pub(crate) fn valid_segments(&self) -> Vec<bool> {
+        let mut last = false;
+        self.checks
+            .iter()
+            .map(|check| {
+                if let Some(check) = check {
+                    last = check.is_valid();
+                }
+                last
+            })
+            .collect()
+    }
+
+    pub(crate) fn num_checked(&self) -> usize {
+        self.checks.iter().filter(|check| check.is_some()).count()
+    }
+}
+
+/// Turns per-segment validity into the runs of valid segments.
+pub fn valid_ranges(is_valid: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = None;
+    for (index, valid) in is_valid.iter().enumerate() {
+        match (start, valid) {
+            (None, true) => start = Some(index),
+            (Some(range_start), false) => {
+                ranges.push(range_start..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(range_start) = start {
+        ranges.push(range_start..is_valid.len());
+    }
+    ranges
+}
+
+/// Like `get_media_playlist_with_valid_segments`, but assumes that valid segments come in long
+/// runs, as they do when the CDN deletes the start or the end of a VOD.
+/// This takes far fewer requests than checking every segment. See `RangeSearch` for how.
+pub async fn get_media_playlist_with_valid_ranges(
+    mut raw_playlist: MediaPlaylist,
+    stride: usize,
+    concurrency: &AimdConfig,
+    client: HttpClient,
+    milliseconds_retry: u64,
+    segment_cache: &mut SegmentCache,
+    policy: &SegmentCachePolicy,
+) -> ValidRangesResponse {
+    let urls = Arc::new(
+        raw_playlist
+            .segments
+            .iter()
+            .map(|segment| String::clone(&segment.uri))
+            .collect::<Vec<_>>(),
+    );
+    let mut search = RangeSearch::new(urls.len(), stride);
+    let mut error = None;
+    loop {
+        let batch = search.next_batch();
+        if batch.is_empty() {
+            break;
+        }
+        let (checks, batch_error) = check_indices(
+            &urls,
+            batch,
+            concurrency,
+            HttpClient::clone(&client),
+            milliseconds_retry,
+            segment_cache,
+            policy,
+        )
+        .await;
+        search.record(checks);
+        if batch_error.is_some() {
+            error = batch_error;
+            break;
+        }
+    }
+    let is_valid = search.valid_segments();
+    raw_playlist.segments = raw_playlist
+        .segments
+        .into_iter()
+        .zip(&is_valid)
+        .filter_map(|(segment, valid)| valid.then_some(segment))
+        .collect();
+    ValidRangesResponse {
+        playlist: raw_playlist,
+        ranges: valid_ranges(&is_valid),
+        num_checked: search.num_checked(),
+        error,
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::SegmentRecord;
+
+/// Runs a search against known segments and returns the result and the number of checks.
+#[cfg(test)]
+fn run_search(segments: &[Option<bool>], stride: usize) -> (Vec<bool>, usize) {
+    let mut search = RangeSearch::new(segments.len(), stride);
+    loop {
+        let batch = search.next_batch();
+        if batch.is_empty() {
+            break;
+        }
+        search.record(batch.into_iter().map(|index| {
+            let check = match segments[index] {
+                Some(valid) => SegmentCheck::Responded(SegmentRecord {
+                    status: if valid { 200 } else { 403 },
+                    size: None,
+                    checked_at: 0,
+                }),
+                None => SegmentCheck::NoResponse,
+            };
+            (index, check)
+        }));
+    }
+    (search.valid_segments(), search.num_checked())
+}
+
+#[test]
+fn test_valid_ranges() {
+    assert_eq!(
+        valid_ranges(&[true, true, false, true, false]),
+        vec![0..2, 3..4]
+    );
+    assert_eq!(valid_ranges(&[false, true]), vec![1..2]);
+    assert!(valid_ranges(&[]).is_empty());
+}
+
+#[test]
+fn test_range_search_finds_boundaries() {
+    // the first 100 and the last 50 segments were deleted
+    let segments = (0..1000)
+        .map(|i| Some((100..950).contains(&i)))
+        .collect::<Vec<_>>();
+    let (is_valid, num_checked) = run_search(&segments, 32);
+    assert_eq!(valid_ranges(&is_valid), vec![100..950]);
+    assert!(num_checked < 60, "{} checks", num_checked);
+}
+
+#[test]
+fn test_range_search_checks_ambiguous_regions_fully() {
+    let mut segments = vec![Some(true); 100];
+    segments[40] = None;
+    segments[45] = Some(false);
+    let (is_valid, num_checked) = run_search(&segments, 10);
+    // 40 gave no answer, so the regions around it were checked one by one
+    assert!(!is_valid[40]);
+    assert!(!is_valid[45]);
+    assert_eq!(valid_ranges(&is_valid), vec![0..40, 41..45, 46..100]);
+    assert_eq!(num_checked, 11 + 18);
+}
+
+#[test]
+fn test_range_search_empty() {
+    assert_eq!(run_search(&[], 10), (vec![], 0));
+}
+
+#[test]
+fn test_range_search_checks_both_halves_around_a_timed_out_middle() {
+    // the first 10 segments were deleted
+    let mut segments = (0..100).map(|i| Some(i >= 10)).collect::<Vec<_>>();
+    // the middle of the first region, where the boundary is searched for, gave no answer
+    segments[16] = None;
+    let (is_valid, num_checked) = run_search(&segments, 32);
+    assert_eq!(valid_ranges(&is_valid), vec![10..16, 17..100]);
+    assert_eq!(num_checked, 5 + 1 + 30);
+}
@@ -0,0 +1,172 @@
+mod tests;
+
+use std::{collections::BTreeMap, fmt::Display, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{DomainWithPath, ValidDwpResponse, VideoData, VideoPath};
+
+/// Urls that past searches resolved, so that searching for the same VOD again can skip probing.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ResolutionCache {
+    pub entries: BTreeMap<String, CachedResolution>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedResolution {
+    pub domain: String,
+    pub url_path: String,
+    pub streamer_name: String,
+    pub video_id: String,
+    /// Unix time of the path that was found, which can differ from the searched time.
+    pub unix_time: i64,
+    /// The playlist as it was when it was last fetched.
+    #[serde(default)]
+    pub body: String,
+    /// Unix time of the last fetch.
+    pub resolved_at: i64,
+}
+
+/// How long a cached playlist is used without fetching it again.
+pub const DEFAULT_RESOLUTION_TTL: Duration = Duration::from_secs(60 * 60);
+
+impl ResolutionCache {
+    /// Searches with different windows can find different urls, so the window is part of the key.
+    pub fn key(video_data: &VideoData, seconds: i64) -> String {
+        format!("{}+{}s", video_data, seconds)
+    }
+
+    pub fn get(&self, video_data: &VideoData, seconds: i64) -> Option<&CachedResolution> {
+        self.entries.get(&Self::key(video_data, seconds))
+    }
+
+    pub fn insert<T: Clone + 'static + Send + Display>(
+        &mut self,
+        video_data: &VideoData,
+        seconds: i64,
+        found: &ValidDwpResponse<T>,
+        now_unix: i64,
+    ) {
+        let found_video_data = &found.dwp.path.video_data;
+        self.entries.insert(
+            Self::key(video_data, seconds),
+            CachedResolution {
+                domain: found.dwp.domain.to_string(),
+                url_path: found.dwp.path.url_path.clone(),
+                streamer_name: found_video_data.streamer_name.to_string(),
+                video_id: found_video_data.video_id.to_string(),
+                unix_time: found_video_data.unix_time_seconds.timestamp(),
+                body: String::from_utf8_lossy(&found.body).to_string(),
+                resolved_at: now_unix,
+            },
+        );
+    }
+
+    /// Returns `false` if there was no entry.
+    pub fn remove(&mut self, video_data: &VideoData, seconds: i64) -> bool {
+        self.entries
+            .remove(&Self::key(video_data, seconds))
+            .is_some()
+    }
+}
+
+impl CachedResolution {
+    /// Whether the cached playlist can be used as it is. Entries written without a body never are.
+    pub fn is_fresh(&self, ttl: Duration, now_unix: i64) -> bool {
+        !self.body.is_empty() && now_unix - self.resolved_at < ttl.as_secs() as i64
+    }
+
+    pub fn to_domain_with_path(&self) -> anyhow::Result<DomainWithPath<Arc<String>>> {
+        let video_data = VideoData {
+            streamer_name: Arc::new(self.streamer_name.clone()),
+            video_id: Arc::new(self.video_id.clone()),
+            unix_time_seconds: NaiveDateTime::from_timestamp_opt(self.unix_time, 0)
+                .context("unix time out of range")?,
+        };
+        Ok(DomainWithPath {
+            domain: Arc::new(self.domain.clone()),
+            path: Arc::new(VideoPath {
+                url_path: self.url_path.clone(),
+                video_data: Arc::new(video_data),
+            }),
+        })
+    }
+}
+
+/// What the CDN last answered for each segment url, so that validation can skip segments it
+/// checked recently.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SegmentCache {
+    pub segments: BTreeMap<String, SegmentRecord>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SegmentRecord {
+    pub status: u16,
+    /// The content length, if the response had one.
+    pub size: Option<u64>,
+    /// Unix time of the check.
+    pub checked_at: i64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentCachePolicy {
+    /// How long a check is trusted for.
+    pub ttl: Duration,
+    /// Check dead segments again even if they were checked within `ttl`.
+    pub recheck_dead: bool,
+}
+
+impl Default for SegmentCachePolicy {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(24 * 60 * 60),
+            recheck_dead: false,
+        }
+    }
+}
+
+impl SegmentRecord {
+    pub fn is_valid(&self) -> bool {
+        self.status == 200
+    }
+
+    /// Whether the check can be trusted: a 200, or a status that the CDN gives for deleted segments.
+    /// Anything else, e.g. a 503, might turn out differently on the next try.
+    pub fn is_definitive(&self) -> bool {
+        matches!(self.status, 200 | 403 | 404 | 410)
+    }
+}
+
+impl SegmentCache {
+    /// Returns whether the segment is valid if the cached check can be trusted under `policy`.
+    pub fn lookup(&self, url: &str, policy: &SegmentCachePolicy, now_unix: i64) -> Option<bool> {
+        let record = self
+            .segments
+            .get(url)
+            .filter(|record| record.is_definitive())?;
+        if now_unix - record.checked_at >= policy.ttl.as_secs() as i64 {
+            return None;
+        }
+        if !record.is_valid() && policy.recheck_dead {
+            return None;
+        }
+        Some(record.is_valid())
+    }
+
+    /// Only keeps checks that can be trusted, see `SegmentRecord::is_definitive`.
+    pub fn record(&mut self, url: &str, record: SegmentRecord) {
+        if !record.is_definitive() {
+            return;
+        }
+        self.segments.insert(url.to_string(), record);
+    }
+
+    /// Drops the records that `lookup` would no longer use, to keep the file small.
+    pub fn prune(&mut self, policy: &SegmentCachePolicy, now_unix: i64) {
+        self.segments
+            .retain(|_, record| now_unix - record.checked_at < policy.ttl.as_secs() as i64);
+    }
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use bytes::Bytes;
+
+#[test]
+fn test_resolution_cache_round_trip() {
+    let video_data = crate::url_path_to_video_data("abc_streamer_123_1664038929").unwrap();
+    let found_video_data = video_data.with_offset(1);
+    let found = ValidDwpResponse {
+        dwp: DomainWithPath {
+            domain: Arc::new("https://a/".to_string()),
+            path: Arc::new(found_video_data.get_video_path(false)),
+        },
+        body: Bytes::from_static(b"#EXTM3U\n"),
+    };
+    let mut cache = ResolutionCache::default();
+    cache.insert(&video_data, 60, &found, 100);
+    assert!(cache.get(&video_data, 1).is_none());
+
+    let cached = cache.get(&video_data, 60).unwrap();
+    let dwp = cached.to_domain_with_path().unwrap();
+    assert_eq!(dwp.get_index_dvr_url(), found.dwp.get_index_dvr_url());
+    assert_eq!(*dwp.path.video_data, *found.dwp.path.video_data);
+    assert_eq!(cached.body, "#EXTM3U\n");
+    assert!(cached.is_fresh(Duration::from_secs(60), 159));
+    assert!(!cached.is_fresh(Duration::from_secs(60), 160));
+
+    assert!(cache.remove(&video_data, 60));
+    assert!(cache.entries.is_empty());
+}
+
+#[test]
+fn test_segment_cache_policy() {
+    let mut cache = SegmentCache::default();
+    let record = |status| SegmentRecord {
+        status,
+        size: None,
+        checked_at: 1000,
+    };
+    cache.record("valid", record(200));
+    cache.record("dead", record(403));
+    let policy = SegmentCachePolicy {
+        ttl: Duration::from_secs(100),
+        recheck_dead: false,
+    };
+    assert_eq!(cache.lookup("valid", &policy, 1050), Some(true));
+    assert_eq!(cache.lookup("dead", &policy, 1050), Some(false));
+    assert_eq!(cache.lookup("unknown", &policy, 1050), None);
+    // too old
+    assert_eq!(cache.lookup("valid", &policy, 1100), None);
+
+    let recheck = SegmentCachePolicy {
+        recheck_dead: true,
+        ..policy
+    };
+    assert_eq!(cache.lookup("valid", &recheck, 1050), Some(true));
+    assert_eq!(cache.lookup("dead", &recheck, 1050), None);
+
+    cache.record(
+        "new",
+        SegmentRecord {
+            checked_at: 1090,
+            ..record(200)
+        },
+    );
+    cache.prune(&policy, 1150);
+    assert_eq!(cache.segments.keys().collect::<Vec<_>>(), vec!["new"]);
+}
@@ -0,0 +1,59 @@
+mod tests;
+
+use std::{fmt::Display, sync::Arc};
+
+use tokio::sync::watch;
+
+/// Returned for every request made through a client whose token was cancelled.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Stops every request of the clients that it is given to, including the ones in flight.
+/// Clones share the same state, so any of them can cancel.
+#[derive(Clone)]
+pub struct CancellationToken {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Completes once `cancel` has been called.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+        // the sender lives as long as `self`, so this can't fail
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Whether `err` means that no more requests should be made at all.
+pub fn is_fatal(err: &anyhow::Error) -> bool {
+    err.is::<Cancelled>() || err.is::<crate::BudgetExhausted>()
+}
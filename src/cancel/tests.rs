@@ -0,0 +1,33 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::testing::HangingTransport;
+#[cfg(test)]
+use crate::{HttpClient, RateLimitConfig};
+#[cfg(test)]
+use std::{sync::Arc, time::Duration};
+
+#[tokio::test]
+async fn test_cancel_stops_requests_in_flight() {
+    let url = "https://cdn.invalid/".to_string();
+    let client =
+        HttpClient::with_transport(Arc::new(HangingTransport), &RateLimitConfig::default());
+    let token = CancellationToken::new();
+    client.set_cancellation_token(token.clone());
+    let request = tokio::task::spawn({
+        let client = client.clone();
+        async move { client.get(&url).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!token.is_cancelled());
+    token.cancel();
+    let err = tokio::time::timeout(Duration::from_secs(1), request)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert!(is_fatal(&err));
+    // later requests fail right away
+    let err = client.get("http://127.0.0.1:1/").await.unwrap_err();
+    assert!(err.is::<Cancelled>());
+}
@@ -0,0 +1,59 @@
+mod tests;
+
+use std::collections::HashSet;
+
+use m3u8_rs::MediaPlaylist;
+
+/// How the valid segments of a playlist changed between two checks, by segment uri.
+#[derive(Debug, Default, PartialEq)]
+pub struct SegmentChanges {
+    /// Valid before but not anymore.
+    pub newly_dead: Vec<String>,
+    /// Missing before but valid now.
+    pub recovered: Vec<String>,
+}
+
+impl SegmentChanges {
+    /// `before` and `after` hold the valid segments of each check, in playlist order.
+    pub fn between(before: &MediaPlaylist, after: &MediaPlaylist) -> Self {
+        let uris = |playlist: &MediaPlaylist| {
+            playlist
+                .segments
+                .iter()
+                .map(|segment| segment.uri.clone())
+                .collect::<HashSet<_>>()
+        };
+        let before_uris = uris(before);
+        let after_uris = uris(after);
+        Self {
+            newly_dead: before
+                .segments
+                .iter()
+                .filter(|segment| !after_uris.contains(&segment.uri))
+                .map(|segment| segment.uri.clone())
+                .collect(),
+            recovered: after
+                .segments
+                .iter()
+                .filter(|segment| !before_uris.contains(&segment.uri))
+                .map(|segment| segment.uri.clone())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newly_dead.is_empty() && self.recovered.is_empty()
+    }
+
+    /// One line per changed segment, e.g. `dead https://.../0.ts`.
+    pub fn to_report(&self) -> String {
+        let mut report = String::new();
+        for uri in &self.newly_dead {
+            report.push_str(&format!("dead {}\n", uri));
+        }
+        for uri in &self.recovered {
+            report.push_str(&format!("recovered {}\n", uri));
+        }
+        report
+    }
+}
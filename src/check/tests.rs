@@ -0,0 +1,15 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::testing::playlist;
+
+#[test]
+fn test_segment_changes() {
+    let before = playlist(&["0.ts", "1.ts", "3.ts"]);
+    let after = playlist(&["1.ts", "2.ts", "3.ts"]);
+    let changes = SegmentChanges::between(&before, &after);
+    assert_eq!(changes.newly_dead, vec!["0.ts"]);
+    assert_eq!(changes.recovered, vec!["2.ts"]);
+    assert_eq!(changes.to_report(), "dead 0.ts\nrecovered 2.ts\n");
+    assert!(SegmentChanges::between(&before, &before).is_empty());
+}
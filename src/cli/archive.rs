@@ -0,0 +1,46 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// Every `.m3u8` file under `dir`, sorted by path.
+/// Symlinked directories aren't followed, so a link back up the tree can't make the walk loop.
+pub fn find_playlists(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut playlists = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "m3u8")
+            {
+                playlists.push(path);
+            }
+        }
+    }
+    playlists.sort();
+    Ok(playlists)
+}
+
+/// The key of a playlist in the monitor history, so that the same playlist reached through
+/// different spellings of its directory shares one history.
+pub fn history_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// `name.m3u8` becomes `name.partial.m3u8`, so that a playlist whose segments weren't all
+/// checked can't be mistaken for a complete result.
+pub fn partial_path(path: &Path) -> PathBuf {
+    path.with_extension("partial.m3u8")
+}
@@ -0,0 +1,598 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use super::DomainStore;
+use anyhow::Context;
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Args,
+};
+use reqwest::{header::HeaderMap, Certificate};
+use serde::{Deserialize, Serialize};
+use vods::{
+    AimdConfig, HarRecorder, HeadStart, ProxyConfig, ProxyRotation, RateLimitConfig,
+    SegmentCachePolicy, TransformPipeline, WatchdogConfig, DEFAULT_HTTP2_KEEP_ALIVE_INTERVAL,
+    DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT, DEFAULT_TIMEOUT,
+};
+
+/// Every setting, each of which can come from the config file, the environment or the command line.
+/// Later sources override earlier ones: defaults, then the config file, then `VODS_*` environment
+/// variables, then command line flags.
+#[derive(Args, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    /// Directory that playlists are written to
+    #[arg(long, global = true, env = "VODS_OUTPUT_DIR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    /// CDN base url to search, e.g. 'https://vod-secure.twitch.tv/' (repeatable, replaces the default list)
+    #[arg(
+        long = "domain",
+        global = true,
+        env = "VODS_DOMAINS",
+        value_delimiter = ','
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    /// Text file with one CDN base url per line, edited by the 'domains' command
+    #[arg(long, global = true, env = "VODS_DOMAINS_FILE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domains_file: Option<PathBuf>,
+    /// JSON file with per-domain hit rates and health, used to decide which domains to probe first
+    #[arg(long, global = true, env = "VODS_DOMAIN_STATS_FILE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_stats_file: Option<PathBuf>,
+    /// JSON file with the urls that past searches resolved, edited by the 'cache' command
+    #[arg(long, global = true, env = "VODS_RESOLUTION_CACHE_FILE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution_cache_file: Option<PathBuf>,
+    /// Minutes that a cached playlist is used for before it is fetched again
+    #[arg(long, global = true, env = "VODS_RESOLUTION_CACHE_TTL_MINUTES")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution_cache_ttl_minutes: Option<u64>,
+    /// JSON file with the last status of every checked segment url
+    #[arg(long, global = true, env = "VODS_SEGMENT_CACHE_FILE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segment_cache_file: Option<PathBuf>,
+    /// Hours that a cached segment check is trusted for
+    #[arg(long, global = true, env = "VODS_SEGMENT_CACHE_TTL_HOURS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segment_cache_ttl_hours: Option<u64>,
+    /// Check segments that were dead again, even if they were checked within the TTL
+    #[arg(long, global = true, env = "VODS_RECHECK_DEAD", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recheck_dead: Option<bool>,
+    /// JSON file with the availability history of the playlists checked by the 'monitor' command
+    #[arg(long, global = true, env = "VODS_MONITOR_HISTORY_FILE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor_history_file: Option<PathBuf>,
+    /// Neither read nor write cached results
+    #[arg(long, global = true, env = "VODS_NO_CACHE", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_cache: Option<bool>,
+    /// Number of historically best domains that start probing before the others
+    #[arg(long, global = true, env = "VODS_HEAD_START_LEADERS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_start_leaders: Option<usize>,
+    /// Milliseconds that the best domains probe alone before the others start
+    #[arg(long, global = true, env = "VODS_HEAD_START_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_start_ms: Option<u64>,
+    /// Check which domains are reachable before trying paths on them
+    #[arg(long, global = true, env = "VODS_PREFLIGHT", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preflight: Option<bool>,
+    /// Send every request to this base url instead of the CDN domains, e.g. 'http://127.0.0.1:8080/'
+    #[arg(long, global = true, env = "VODS_BASE_URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// HTTP client timeout in milliseconds
+    #[arg(long, global = true, env = "VODS_CLIENT_TIMEOUT_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_timeout_ms: Option<u64>,
+    /// Delay in milliseconds before a failed request is retried
+    #[arg(long, global = true, env = "VODS_RETRY_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_ms: Option<u64>,
+    /// HTTP/2 keep-alive timeout in milliseconds
+    #[arg(long, global = true, env = "VODS_HTTP2_KEEP_ALIVE_TIMEOUT_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_timeout_ms: Option<u64>,
+    /// HTTP/2 keep-alive ping interval in milliseconds
+    #[arg(long, global = true, env = "VODS_HTTP2_KEEP_ALIVE_INTERVAL_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_interval_ms: Option<u64>,
+    /// Number of concurrent segment checks to start with
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_INITIAL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_initial: Option<usize>,
+    /// Lowest number of concurrent segment checks after backing off
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_MIN")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_min: Option<usize>,
+    /// Highest number of concurrent segment checks
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_MAX")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_max: Option<usize>,
+    /// Factor the number of concurrent segment checks is multiplied by on a timeout, reset, 429 or 503
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_DECREASE_FACTOR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_decrease_factor: Option<f64>,
+    /// Segment checks slower than this many milliseconds don't raise the concurrency
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_LATENCY_THRESHOLD_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_latency_threshold_ms: Option<u64>,
+    /// Share of failed segment checks, smoothed, above which the concurrency is lowered
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_ERROR_RATE_THRESHOLD")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_error_rate_threshold: Option<f64>,
+    /// Minimum number of milliseconds between two decreases of the concurrency
+    #[arg(long, global = true, env = "VODS_CONCURRENCY_DECREASE_COOLDOWN_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_decrease_cooldown_ms: Option<u64>,
+    /// Maximum number of requests per second across all network operations
+    #[arg(long, global = true, env = "VODS_REQUESTS_PER_SECOND")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+    /// Maximum number of response body bytes read per second. Only playlists are read;
+    /// segment checks don't download the segments
+    #[arg(long, global = true, env = "VODS_BYTES_PER_SECOND")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_per_second: Option<f64>,
+    /// Total number of requests after which the tool stops and keeps whatever it has found
+    #[arg(long, global = true, env = "VODS_REQUEST_BUDGET")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_budget: Option<u64>,
+    /// Rebuild the HTTP client if no request has completed for this many milliseconds
+    #[arg(long, global = true, env = "VODS_STALL_TIMEOUT_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stall_timeout_ms: Option<u64>,
+    /// Rebuild the HTTP client after this many connection errors or timeouts in a row
+    #[arg(long, global = true, env = "VODS_MAX_CONSECUTIVE_CONNECTION_ERRORS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_consecutive_connection_errors: Option<u32>,
+    /// Make the rebuilt HTTP clients HTTP/1.1 only
+    #[arg(long, global = true, env = "VODS_HTTP1_FALLBACK", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http1_fallback: Option<bool>,
+    /// HTTP or SOCKS5 proxy to add to the proxy pool, e.g. 'socks5://127.0.0.1:1080' (repeatable)
+    #[arg(
+        long = "proxy",
+        global = true,
+        env = "VODS_PROXIES",
+        value_delimiter = ','
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proxies: Vec<String>,
+    /// How requests are spread over the proxy pool: per-request rotates on every request,
+    /// per-domain keeps every host on the same proxy
+    #[arg(long, global = true, env = "VODS_PROXY_ROTATION", value_parser = proxy_rotation_parser())]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_rotation: Option<ProxyRotation>,
+    /// Always send requests for a domain through a proxy, e.g. 'vod-secure.twitch.tv=http://10.0.0.1:3128' (repeatable)
+    #[arg(
+        long = "proxy-for",
+        global = true,
+        env = "VODS_PROXY_FOR",
+        value_delimiter = ','
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proxy_for: Vec<String>,
+    /// User-Agent header sent with every request
+    #[arg(long, global = true, env = "VODS_USER_AGENT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Extra header sent with every request, e.g. 'Authorization: Bearer token' (repeatable)
+    #[arg(
+        long = "header",
+        global = true,
+        env = "VODS_HEADERS",
+        value_delimiter = '\n'
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<String>,
+    /// PEM or DER file with an extra trusted root certificate, e.g. a corporate CA (repeatable)
+    #[arg(
+        long = "root-cert",
+        global = true,
+        env = "VODS_ROOT_CERTS",
+        value_delimiter = ','
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub root_certs: Vec<PathBuf>,
+    /// Only use HTTP/1.1. This can be more robust than HTTP/2 when the network changes.
+    #[arg(long, global = true, env = "VODS_HTTP1_ONLY", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http1_only: Option<bool>,
+    /// Write every request and response, with the bodies of the playlists, to this HAR file when
+    /// the command finishes. The caches and the domain stats are neither read nor written, so
+    /// that the whole search is recorded
+    #[arg(long, global = true, env = "VODS_RECORD", conflicts_with = "replay")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<PathBuf>,
+    /// Answer every request from this HAR file instead of the network. The caches and the
+    /// domain stats are neither read nor written
+    #[arg(long, global = true, env = "VODS_REPLAY")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay: Option<PathBuf>,
+    /// Steps applied to the playlist before it is written, in order, e.g.
+    /// 'mute,explicit,filter,trim=10m-1h'. Available: mute, unmute, explicit, relative, filter,
+    /// trim=START-END, discontinuities, program-date-time
+    #[arg(long, global = true, env = "VODS_TRANSFORMS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transforms: Option<String>,
+    /// Stamp every segment with the UTC time at which it was streamed (EXT-X-PROGRAM-DATE-TIME),
+    /// and mark the gaps left by removed segments
+    #[arg(long, global = true, env = "VODS_PROGRAM_DATE_TIME", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_date_time: Option<bool>,
+}
+
+impl Settings {
+    pub fn defaults() -> Self {
+        let concurrency = AimdConfig::default();
+        let watchdog = WatchdogConfig::default();
+        Self {
+            output_dir: Some(PathBuf::from("Downloads")),
+            domains: vec![],
+            domains_file: default_data_dir().map(|dir| dir.join("domains.txt")),
+            base_url: None,
+            domain_stats_file: default_data_dir().map(|dir| dir.join("domain-stats.json")),
+            resolution_cache_file: default_data_dir().map(|dir| dir.join("resolutions.json")),
+            resolution_cache_ttl_minutes: Some(vods::DEFAULT_RESOLUTION_TTL.as_secs() / 60),
+            segment_cache_file: default_data_dir().map(|dir| dir.join("segments.json")),
+            segment_cache_ttl_hours: Some(SegmentCachePolicy::default().ttl.as_secs() / (60 * 60)),
+            recheck_dead: Some(SegmentCachePolicy::default().recheck_dead),
+            monitor_history_file: default_data_dir().map(|dir| dir.join("monitor-history.json")),
+            no_cache: Some(false),
+            head_start_leaders: Some(3),
+            head_start_ms: Some(250),
+            preflight: Some(true),
+            client_timeout_ms: Some(DEFAULT_TIMEOUT.as_millis() as u64),
+            retry_ms: Some(2000),
+            http2_keep_alive_timeout_ms: Some(DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT.as_millis() as u64),
+            http2_keep_alive_interval_ms: Some(DEFAULT_HTTP2_KEEP_ALIVE_INTERVAL.as_millis() as u64),
+            concurrency_initial: Some(concurrency.initial),
+            concurrency_min: Some(concurrency.min),
+            concurrency_max: Some(concurrency.max),
+            concurrency_decrease_factor: Some(concurrency.decrease_factor),
+            concurrency_latency_threshold_ms: Some(concurrency.latency_threshold.as_millis() as u64),
+            concurrency_error_rate_threshold: Some(concurrency.error_rate_threshold),
+            concurrency_decrease_cooldown_ms: Some(concurrency.decrease_cooldown.as_millis() as u64),
+            requests_per_second: None,
+            bytes_per_second: None,
+            request_budget: None,
+            stall_timeout_ms: Some(watchdog.stall_timeout.as_millis() as u64),
+            max_consecutive_connection_errors: Some(watchdog.max_consecutive_connection_errors),
+            http1_fallback: Some(watchdog.fall_back_to_http1),
+            proxies: vec![],
+            proxy_rotation: Some(ProxyRotation::default()),
+            proxy_for: vec![],
+            user_agent: None,
+            headers: vec![],
+            root_certs: vec![],
+            http1_only: Some(false),
+            record: None,
+            replay: None,
+            transforms: Some(TransformPipeline::default().to_string()),
+            program_date_time: Some(false),
+        }
+    }
+
+    /// Returns `self` with every value that is set in `other` replaced.
+    pub fn merge(self, other: Settings) -> Settings {
+        fn list<T>(base: Vec<T>, other: Vec<T>) -> Vec<T> {
+            if other.is_empty() {
+                base
+            } else {
+                other
+            }
+        }
+        Settings {
+            output_dir: other.output_dir.or(self.output_dir),
+            domains: list(self.domains, other.domains),
+            domains_file: other.domains_file.or(self.domains_file),
+            base_url: other.base_url.or(self.base_url),
+            domain_stats_file: other.domain_stats_file.or(self.domain_stats_file),
+            resolution_cache_file: other.resolution_cache_file.or(self.resolution_cache_file),
+            resolution_cache_ttl_minutes: other
+                .resolution_cache_ttl_minutes
+                .or(self.resolution_cache_ttl_minutes),
+            segment_cache_file: other.segment_cache_file.or(self.segment_cache_file),
+            segment_cache_ttl_hours: other
+                .segment_cache_ttl_hours
+                .or(self.segment_cache_ttl_hours),
+            recheck_dead: other.recheck_dead.or(self.recheck_dead),
+            monitor_history_file: other.monitor_history_file.or(self.monitor_history_file),
+            no_cache: other.no_cache.or(self.no_cache),
+            head_start_leaders: other.head_start_leaders.or(self.head_start_leaders),
+            head_start_ms: other.head_start_ms.or(self.head_start_ms),
+            preflight: other.preflight.or(self.preflight),
+            client_timeout_ms: other.client_timeout_ms.or(self.client_timeout_ms),
+            retry_ms: other.retry_ms.or(self.retry_ms),
+            http2_keep_alive_timeout_ms: other
+                .http2_keep_alive_timeout_ms
+                .or(self.http2_keep_alive_timeout_ms),
+            http2_keep_alive_interval_ms: other
+                .http2_keep_alive_interval_ms
+                .or(self.http2_keep_alive_interval_ms),
+            concurrency_initial: other.concurrency_initial.or(self.concurrency_initial),
+            concurrency_min: other.concurrency_min.or(self.concurrency_min),
+            concurrency_max: other.concurrency_max.or(self.concurrency_max),
+            concurrency_decrease_factor: other
+                .concurrency_decrease_factor
+                .or(self.concurrency_decrease_factor),
+            concurrency_latency_threshold_ms: other
+                .concurrency_latency_threshold_ms
+                .or(self.concurrency_latency_threshold_ms),
+            concurrency_error_rate_threshold: other
+                .concurrency_error_rate_threshold
+                .or(self.concurrency_error_rate_threshold),
+            concurrency_decrease_cooldown_ms: other
+                .concurrency_decrease_cooldown_ms
+                .or(self.concurrency_decrease_cooldown_ms),
+            requests_per_second: other.requests_per_second.or(self.requests_per_second),
+            bytes_per_second: other.bytes_per_second.or(self.bytes_per_second),
+            request_budget: other.request_budget.or(self.request_budget),
+            stall_timeout_ms: other.stall_timeout_ms.or(self.stall_timeout_ms),
+            max_consecutive_connection_errors: other
+                .max_consecutive_connection_errors
+                .or(self.max_consecutive_connection_errors),
+            http1_fallback: other.http1_fallback.or(self.http1_fallback),
+            proxies: list(self.proxies, other.proxies),
+            proxy_rotation: other.proxy_rotation.or(self.proxy_rotation),
+            proxy_for: list(self.proxy_for, other.proxy_for),
+            user_agent: other.user_agent.or(self.user_agent),
+            headers: list(self.headers, other.headers),
+            root_certs: list(self.root_certs, other.root_certs),
+            http1_only: other.http1_only.or(self.http1_only),
+            record: other.record.or(self.record),
+            replay: other.replay.or(self.replay),
+            transforms: other.transforms.or(self.transforms),
+            program_date_time: other.program_date_time.or(self.program_date_time),
+        }
+    }
+
+    /// Parses the settings from the TOML file at `path`.
+    /// Relative certificate paths are resolved relative to the file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Settings> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let mut settings: Settings = toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            for cert in &mut settings.root_certs {
+                if cert.is_relative() {
+                    *cert = dir.join(&cert);
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/vods/config.toml`, falling back to `~/.config/vods/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("vods").join("config.toml"))
+}
+
+/// `$XDG_DATA_HOME/vods`, falling back to `~/.local/share/vods`.
+pub fn default_data_dir() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+    Some(data_home.join("vods"))
+}
+
+/// Combines the defaults, the config file and the command line (which already includes the
+/// environment). An explicitly requested config file must exist; the default one may not.
+/// If no domains are configured, they come from the domains file or the built-in list.
+pub fn resolve_settings(
+    config_path: Option<&Path>,
+    cli: Settings,
+) -> anyhow::Result<(Settings, Option<PathBuf>)> {
+    let (file_settings, loaded_path) = match config_path {
+        Some(path) => (Settings::from_file(path)?, Some(path.to_path_buf())),
+        None => match default_config_path() {
+            Some(path) if path.exists() => (Settings::from_file(&path)?, Some(path)),
+            _ => (Settings::default(), None),
+        },
+    };
+    let mut settings = Settings::defaults().merge(file_settings).merge(cli);
+    settings.domains = if settings.domains.is_empty() {
+        match &settings.domains_file {
+            Some(path) => DomainStore::open(path)?.domains,
+            None => DomainStore::default_domains(),
+        }
+    } else {
+        settings
+            .domains
+            .iter()
+            .map(|domain| normalize_domain(domain))
+            .collect()
+    };
+    Ok((settings, loaded_path))
+}
+
+/// The effective configuration that the rest of the binary uses.
+#[derive(Clone)]
+pub struct Config {
+    pub output_dir: PathBuf,
+    pub domains: Vec<Arc<String>>,
+    /// Where the domains of VOD urls are learned. `None` when replaying, since the replayed
+    /// traffic shouldn't change the user's domains.
+    pub domains_file: Option<PathBuf>,
+    pub domain_stats_file: Option<PathBuf>,
+    /// `None` if caching is turned off.
+    pub resolution_cache_file: Option<PathBuf>,
+    pub resolution_cache_ttl: Duration,
+    /// `None` if caching is turned off.
+    pub segment_cache_file: Option<PathBuf>,
+    pub segment_cache: SegmentCachePolicy,
+    pub monitor_history_file: Option<PathBuf>,
+    pub head_start: HeadStart,
+    pub preflight: bool,
+    pub concurrency: AimdConfig,
+    pub client_timeout_milliseconds: u64,
+    pub milliseconds_retry: u64,
+    pub http2_keep_alive_timeout_milliseconds: u64,
+    pub http2_keep_alive_interval_milliseconds: u64,
+    pub rate_limit: RateLimitConfig,
+    pub watchdog: WatchdogConfig,
+    pub proxy: ProxyConfig,
+    pub user_agent: Option<String>,
+    pub headers: HeaderMap,
+    pub root_certificates: Vec<Certificate>,
+    pub http1_only: bool,
+    /// Set if the traffic is recorded to `record_file`.
+    pub har_recorder: Option<HarRecorder>,
+    pub record_file: Option<PathBuf>,
+    pub replay_file: Option<PathBuf>,
+    pub transforms: TransformPipeline,
+}
+
+impl TryFrom<Settings> for Config {
+    type Error = anyhow::Error;
+
+    /// `settings` is expected to be merged on top of `Settings::defaults()`.
+    fn try_from(settings: Settings) -> Result<Self, Self::Error> {
+        if settings.record.is_some() && settings.replay.is_some() {
+            return Err(anyhow::anyhow!("a replay can't be recorded"));
+        }
+        // a recording has to contain the whole search, and a replay mustn't leave anything behind
+        let use_cache = !settings.no_cache.unwrap_or_default()
+            && settings.record.is_none()
+            && settings.replay.is_none();
+        let mut transforms: TransformPipeline = settings
+            .transforms
+            .as_deref()
+            .unwrap_or_default()
+            .parse()
+            .context("invalid transforms")?;
+        if settings.program_date_time.unwrap_or_default() {
+            transforms = transforms.with_program_date_time();
+        }
+        Ok(Config {
+            output_dir: settings.output_dir.unwrap_or_default(),
+            domains_file: settings.domains_file.filter(|_| settings.replay.is_none()),
+            domains: match settings.base_url {
+                Some(base_url) => vec![Arc::new(normalize_domain(&base_url))],
+                None => settings
+                    .domains
+                    .iter()
+                    .map(|domain| Arc::new(normalize_domain(domain)))
+                    .collect(),
+            },
+            domain_stats_file: settings.domain_stats_file.filter(|_| use_cache),
+            resolution_cache_file: settings.resolution_cache_file.filter(|_| use_cache),
+            resolution_cache_ttl: Duration::from_secs(
+                settings.resolution_cache_ttl_minutes.unwrap_or_default() * 60,
+            ),
+            segment_cache_file: settings.segment_cache_file.filter(|_| use_cache),
+            monitor_history_file: settings.monitor_history_file,
+            segment_cache: SegmentCachePolicy {
+                ttl: Duration::from_secs(
+                    settings.segment_cache_ttl_hours.unwrap_or_default() * 60 * 60,
+                ),
+                recheck_dead: settings.recheck_dead.unwrap_or_default(),
+            },
+            head_start: HeadStart {
+                leaders: settings.head_start_leaders.unwrap_or_default(),
+                delay: Duration::from_millis(settings.head_start_ms.unwrap_or_default()),
+            },
+            preflight: settings.preflight.unwrap_or_default(),
+            concurrency: AimdConfig {
+                initial: settings.concurrency_initial.unwrap_or_default(),
+                min: settings.concurrency_min.unwrap_or_default(),
+                max: settings.concurrency_max.unwrap_or_default(),
+                decrease_factor: settings.concurrency_decrease_factor.unwrap_or_default(),
+                latency_threshold: Duration::from_millis(
+                    settings
+                        .concurrency_latency_threshold_ms
+                        .unwrap_or_default(),
+                ),
+                error_rate_threshold: settings
+                    .concurrency_error_rate_threshold
+                    .unwrap_or_default(),
+                decrease_cooldown: Duration::from_millis(
+                    settings
+                        .concurrency_decrease_cooldown_ms
+                        .unwrap_or_default(),
+                ),
+            },
+            client_timeout_milliseconds: settings.client_timeout_ms.unwrap_or_default(),
+            milliseconds_retry: settings.retry_ms.unwrap_or_default(),
+            http2_keep_alive_timeout_milliseconds: settings
+                .http2_keep_alive_timeout_ms
+                .unwrap_or_default(),
+            http2_keep_alive_interval_milliseconds: settings
+                .http2_keep_alive_interval_ms
+                .unwrap_or_default(),
+            rate_limit: RateLimitConfig {
+                requests_per_second: settings.requests_per_second,
+                bytes_per_second: settings.bytes_per_second,
+                max_requests: settings.request_budget,
+            },
+            watchdog: WatchdogConfig {
+                stall_timeout: Duration::from_millis(settings.stall_timeout_ms.unwrap_or_default()),
+                max_consecutive_connection_errors: settings
+                    .max_consecutive_connection_errors
+                    .unwrap_or_default(),
+                fall_back_to_http1: settings.http1_fallback.unwrap_or_default(),
+            },
+            proxy: ProxyConfig {
+                proxies: settings.proxies,
+                rotation: settings.proxy_rotation.unwrap_or_default(),
+                assignments: settings
+                    .proxy_for
+                    .iter()
+                    .map(|assignment| vods::parse_proxy_assignment(assignment))
+                    .collect::<anyhow::Result<_>>()?,
+            },
+            user_agent: settings.user_agent,
+            headers: settings
+                .headers
+                .iter()
+                .map(|header| vods::parse_header(header))
+                .collect::<anyhow::Result<_>>()?,
+            root_certificates: settings
+                .root_certs
+                .iter()
+                .map(|path| vods::load_root_certificate(path))
+                .collect::<anyhow::Result<_>>()?,
+            http1_only: settings.http1_only.unwrap_or_default(),
+            har_recorder: settings.record.as_ref().map(|_| HarRecorder::new()),
+            record_file: settings.record,
+            replay_file: settings.replay,
+            transforms,
+        })
+    }
+}
+
+/// Parses `--proxy-rotation`, so that the library's `ProxyRotation` doesn't depend on clap.
+fn proxy_rotation_parser() -> impl TypedValueParser<Value = ProxyRotation> {
+    PossibleValuesParser::new(["per-request", "per-domain"]).map(|value| match value.as_str() {
+        "per-domain" => ProxyRotation::PerDomain,
+        _ => ProxyRotation::PerRequest,
+    })
+}
+
+/// Makes sure a domain ends in a slash so that paths can be appended to it.
+pub fn normalize_domain(domain: &str) -> String {
+    if domain.ends_with('/') {
+        domain.to_string()
+    } else {
+        format!("{}/", domain)
+    }
+}
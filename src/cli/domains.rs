@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use chrono::NaiveDateTime;
+use url::Url;
+use vods::DomainStats;
+
+use super::normalize_domain;
+
+/// The CDN domains kept in a text file with one base url per line.
+/// Blank lines and lines starting with `#` are ignored.
+pub struct DomainStore {
+    path: PathBuf,
+    pub domains: Vec<String>,
+}
+
+impl DomainStore {
+    pub fn default_domains() -> Vec<String> {
+        vods::DOMAINS
+            .iter()
+            .map(|domain| domain.to_string())
+            .collect()
+    }
+
+    /// Loads the file at `path`, or starts from the built-in domains if it doesn't exist yet.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let domains = if path.exists() {
+            fs::read_to_string(path)
+                .with_context(|| format!("failed to read domains file {}", path.display()))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(normalize_domain)
+                .collect()
+        } else {
+            Self::default_domains()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            domains,
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = self.domains.join("\n");
+        contents.push('\n');
+        fs::write(&self.path, contents)
+            .with_context(|| format!("failed to write domains file {}", self.path.display()))
+    }
+
+    /// Returns `false` if the domain was already present.
+    pub fn add(&mut self, domain: &str) -> bool {
+        let domain = normalize_domain(domain);
+        if self.domains.contains(&domain) {
+            return false;
+        }
+        self.domains.push(domain);
+        true
+    }
+
+    /// Returns `false` if the domain wasn't present.
+    pub fn remove(&mut self, domain: &str) -> bool {
+        let domain = normalize_domain(domain);
+        let len = self.domains.len();
+        self.domains.retain(|existing| existing != &domain);
+        self.domains.len() != len
+    }
+
+    /// Remembers a domain seen in a VOD url, saving the file if it is new.
+    pub fn learn(&mut self, domain: &str) -> anyhow::Result<()> {
+        if self.add(domain) {
+            self.save()?;
+            println!("Learned new domain {}", domain);
+        }
+        Ok(())
+    }
+}
+
+/// Accepts a base url like `https://vod-secure.twitch.tv/` or a full VOD url, returning the base url.
+pub fn domain_from_argument(argument: &str) -> anyhow::Result<String> {
+    if let Ok(dwp) = vods::url_to_domain_with_path(argument) {
+        return Ok(dwp.domain.to_string());
+    }
+    let parsed = Url::parse(argument)?;
+    if parsed.host_str().is_none() {
+        return Err(anyhow!("{} has no host", argument));
+    }
+    Ok(normalize_domain(&parsed[..url::Position::BeforePath]))
+}
+
+/// Prints one row per domain with hits/searches for each VOD age range.
+pub fn print_domain_stats(stats: &DomainStats, domains: &[String]) {
+    let mut header = format!("{:<42}", "DOMAIN");
+    let mut lower = 0;
+    for upper in vods::AGE_BUCKETS_DAYS {
+        header.push_str(&format!(" {:>9}", format!("{}-{}d", lower, upper)));
+        lower = upper;
+    }
+    header.push_str(&format!(
+        " {:>9} {:>10} {:>8} {:>10}",
+        format!("{}d+", lower),
+        "LATENCY",
+        "FAILURES",
+        "LAST HIT"
+    ));
+    println!("{}", header);
+    // configured domains first, then anything else that has history
+    let mut names = domains.to_vec();
+    names.extend(
+        stats
+            .domains
+            .keys()
+            .filter(|name| !domains.contains(name))
+            .cloned(),
+    );
+    for name in names {
+        let record = stats.domains.get(&name).cloned().unwrap_or_default();
+        let mut row = format!("{:<42}", name);
+        for bucket in 0..=vods::AGE_BUCKETS_DAYS.len() {
+            let counts = record.buckets.get(bucket).copied().unwrap_or_default();
+            row.push_str(&format!(
+                " {:>9}",
+                format!("{}/{}", counts.hits, counts.searches)
+            ));
+        }
+        let latency = record
+            .average_latency_milliseconds
+            .map(|latency| format!("{:.0}ms", latency))
+            .unwrap_or_else(|| "-".to_string());
+        let last_hit = record
+            .last_hit
+            .and_then(|time| NaiveDateTime::from_timestamp_opt(time, 0))
+            .map(|time| time.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        row.push_str(&format!(
+            " {:>10} {:>8.1} {:>10}",
+            latency, record.recent_failures, last_hit
+        ));
+        println!("{}", row);
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use vods::{FakeCdnConfig, FakeReply};
+
+/// Answers HTTP/1.1 requests on `listener` from `config` until the listener fails.
+/// Connections are kept alive so that clients behave like they do against the real CDN.
+pub async fn serve_fake_cdn(
+    config: Arc<FakeCdnConfig>,
+    listener: TcpListener,
+    log_requests: bool,
+) -> anyhow::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::task::spawn(handle_connection(Arc::clone(&config), stream, log_requests));
+    }
+}
+
+async fn handle_connection(config: Arc<FakeCdnConfig>, mut stream: TcpStream, log_requests: bool) {
+    let mut buffer = vec![];
+    loop {
+        let head_end = loop {
+            if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break position + 4;
+            }
+            let mut chunk = [0; 4096];
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        };
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        buffer.drain(..head_end);
+        let path = head.split(' ').nth(1).unwrap_or("/");
+        let reply = config.respond(path, &mut rand::thread_rng());
+        if log_requests {
+            match &reply {
+                FakeReply::Respond { status, .. } => println!("GET {} {}", path, status),
+                FakeReply::Timeout => println!("GET {} timeout", path),
+                FakeReply::Reset => println!("GET {} reset", path),
+            }
+        }
+        match reply {
+            FakeReply::Respond { status, body } => {
+                let reason = StatusCode::from_u16(status)
+                    .ok()
+                    .and_then(|status| status.canonical_reason())
+                    .unwrap_or("Unknown");
+                let content_type = if path.ends_with(".m3u8") {
+                    "application/vnd.apple.mpegurl"
+                } else {
+                    "video/mp2t"
+                };
+                let head = format!(
+                    "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                    status,
+                    reason,
+                    content_type,
+                    body.len()
+                );
+                if stream.write_all(head.as_bytes()).await.is_err()
+                    || stream.write_all(&body).await.is_err()
+                {
+                    return;
+                }
+            }
+            FakeReply::Timeout => {
+                // hold the connection open until the client gives up
+                let mut chunk = [0; 4096];
+                while let Ok(1..) = stream.read(&mut chunk).await {}
+                return;
+            }
+            FakeReply::Reset => return,
+        }
+    }
+}
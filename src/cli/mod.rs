@@ -0,0 +1,13 @@
+mod archive;
+mod config;
+mod domains;
+mod fakecdn;
+mod progress;
+mod state;
+mod tests;
+pub use archive::*;
+pub use config::*;
+pub use domains::*;
+pub use fakecdn::*;
+pub use progress::*;
+pub use state::*;
@@ -0,0 +1,188 @@
+use std::{
+    io::{stderr, IsTerminal, Write},
+    sync::{Arc, Mutex},
+};
+
+use vods::{Event, EventSink, HttpClient};
+
+static CLEAR_LINE: &str = "\x1b[2K";
+const BAR_WIDTH: usize = 30;
+
+/// Draws the events of a client as a progress line on stderr.
+/// If stderr isn't a terminal, only the messages are written, without the progress line.
+pub struct ProgressBar {
+    is_terminal: bool,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    candidates: usize,
+    bytes: usize,
+    /// Whether the cursor is at the end of a progress line.
+    line_open: bool,
+}
+
+impl ProgressBar {
+    /// Makes `client` report to a new progress bar.
+    pub fn attach(client: &HttpClient) -> Arc<Self> {
+        let progress = Arc::new(Self {
+            is_terminal: stderr().is_terminal(),
+            state: Mutex::new(ProgressState::default()),
+        });
+        client.set_event_sink(progress.clone());
+        progress
+    }
+
+    /// Ends the current progress line so that other output starts on a new line.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.candidates = 0;
+        Self::end_line(&mut state);
+    }
+
+    fn end_line(state: &mut ProgressState) {
+        if state.line_open {
+            eprintln!();
+            state.line_open = false;
+        }
+    }
+
+    fn draw(&self, state: &mut ProgressState, line: String) {
+        if !self.is_terminal {
+            return;
+        }
+        eprint!("\r{}{}", CLEAR_LINE, line);
+        let _ = stderr().flush();
+        state.line_open = true;
+    }
+
+    fn message(state: &mut ProgressState, message: String) {
+        Self::end_line(state);
+        eprintln!("{}", message);
+    }
+}
+
+fn human_readable_bytes(bytes: usize) -> String {
+    let mut value = bytes as f64;
+    for unit in ["B", "kB", "MB", "GB"] {
+        if value < 1000.0 {
+            return format!("{:.1} {}", value, unit);
+        }
+        value /= 1000.0;
+    }
+    format!("{:.1} TB", value)
+}
+
+impl EventSink for ProgressBar {
+    fn on_event(&self, event: &Event) {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            Event::DomainUnreachable { domain, error } => Self::message(
+                &mut state,
+                format!("Warning: skipping unreachable domain {}: {}", domain, error),
+            ),
+            Event::KnownUrlChecked { url, cached, error } => match (cached, error) {
+                (false, None) => {}
+                (false, Some(error)) => Self::message(
+                    &mut state,
+                    format!("{} is not valid, searching: {}", url, error),
+                ),
+                (true, None) => Self::message(&mut state, format!("Using cached url {}", url)),
+                (true, Some(error)) => Self::message(
+                    &mut state,
+                    format!("Cached url {} is no longer valid: {}", url, error),
+                ),
+            },
+            Event::CachedPlaylistUsed { url, age } => Self::message(
+                &mut state,
+                format!(
+                    "Using the playlist of {} cached {} ago",
+                    url,
+                    crate::duration_to_human_readable(age)
+                ),
+            ),
+            Event::CachedPlaylistChanged { url } => Self::message(
+                &mut state,
+                format!("The playlist at {} changed since it was cached", url),
+            ),
+            Event::CandidateTried { .. } => {
+                state.candidates += 1;
+                let line = format!("Tried {} candidate urls", state.candidates);
+                self.draw(&mut state, line);
+            }
+            Event::DomainResult { .. } => {}
+            Event::UrlFound { url, proxy } => {
+                state.candidates = 0;
+                let message = match proxy {
+                    Some(proxy) => format!("Found valid url {} via proxy {}", url, proxy),
+                    None => format!("Found valid url {}", url),
+                };
+                Self::message(&mut state, message);
+            }
+            Event::SampleEstimated {
+                estimate,
+                escalating,
+            } => {
+                Self::message(
+                    &mut state,
+                    format!(
+                        "Estimated {:.1}% of segments available ({:.1}% to {:.1}% at 95% confidence), {} valid out of {} sampled",
+                        estimate.estimate * 100.0,
+                        estimate.low * 100.0,
+                        estimate.high * 100.0,
+                        estimate.valid,
+                        estimate.checked
+                    ),
+                );
+                if *escalating {
+                    Self::message(
+                        &mut state,
+                        "The sample found invalid segments, checking every segment".to_string(),
+                    );
+                }
+            }
+            Event::SegmentsCached { count, total } => Self::message(
+                &mut state,
+                format!(
+                    "Using cached checks for {} segments out of {}",
+                    count, total
+                ),
+            ),
+            Event::SegmentChecked {
+                done,
+                total,
+                concurrency,
+                ..
+            } => {
+                let filled = BAR_WIDTH * done / (*total).max(1);
+                let line = format!(
+                    "[{}{}] {}/{} segments (concurrency {}, {} downloaded)",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    done,
+                    total,
+                    concurrency,
+                    human_readable_bytes(state.bytes)
+                );
+                self.draw(&mut state, line);
+                if done == total {
+                    Self::end_line(&mut state);
+                }
+            }
+            Event::BytesDownloaded { bytes, .. } => state.bytes += bytes,
+            Event::ClientRebuilt { reason, http1_only } => Self::message(
+                &mut state,
+                format!(
+                    "Rebuilding HTTP client ({}){}",
+                    reason,
+                    if *http1_only { " in HTTP/1.1 mode" } else { "" }
+                ),
+            ),
+            Event::ClientRebuildFailed { error } => Self::message(
+                &mut state,
+                format!("Failed to rebuild HTTP client: {}", error),
+            ),
+        }
+    }
+}
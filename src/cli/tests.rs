@@ -0,0 +1,152 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[test]
+fn test_settings_precedence() {
+    let file: Settings = toml::from_str(
+        r#"
+        retry-ms = 100
+        concurrency-max = 10
+        concurrency-decrease-factor = 0.75
+        max-consecutive-connection-errors = 3
+        domains = ["http://127.0.0.1:8080"]
+        "#,
+    )
+    .unwrap();
+    let cli = Settings {
+        concurrency_max: Some(20),
+        ..Settings::default()
+    };
+    let settings = Settings::defaults().merge(file).merge(cli);
+    assert_eq!(settings.retry_ms, Some(100));
+    assert_eq!(settings.concurrency_max, Some(20));
+    assert_eq!(settings.client_timeout_ms, Some(5000));
+    let config = Config::try_from(settings).unwrap();
+    assert_eq!(
+        config.domains,
+        vec![Arc::new("http://127.0.0.1:8080/".to_string())]
+    );
+    assert_eq!(config.concurrency.decrease_factor, 0.75);
+    assert_eq!(
+        config.concurrency.latency_threshold,
+        vods::AimdConfig::default().latency_threshold
+    );
+    assert_eq!(config.watchdog.max_consecutive_connection_errors, 3);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_find_playlists_skips_symlinked_directories() {
+    let dir = std::env::temp_dir().join(format!("vods-find-playlists-{}", std::process::id()));
+    let nested = dir.join("streamer");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(nested.join("a.m3u8"), "#EXTM3U\n").unwrap();
+    std::fs::write(nested.join("notes.txt"), "").unwrap();
+    std::os::unix::fs::symlink(&dir, nested.join("loop")).unwrap();
+
+    let playlists = find_playlists(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(playlists.unwrap(), vec![nested.join("a.m3u8")]);
+}
+
+#[test]
+fn test_history_key_ignores_how_the_directory_is_spelled() {
+    let dir = std::env::temp_dir().join(format!("vods-history-key-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.m3u8"), "#EXTM3U\n").unwrap();
+
+    let plain = history_key(&dir.join("a.m3u8"));
+    let dotted = history_key(&dir.join(".").join("a.m3u8"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(plain, dotted);
+}
+
+#[test]
+fn test_partial_path() {
+    assert_eq!(
+        partial_path(Path::new("Downloads/streamer/a_01h00m00s.m3u8")),
+        PathBuf::from("Downloads/streamer/a_01h00m00s.partial.m3u8")
+    );
+}
+
+#[test]
+fn test_replay_skips_the_caches() {
+    let files = Settings {
+        domain_stats_file: Some("domain-stats.json".into()),
+        resolution_cache_file: Some("resolutions.json".into()),
+        segment_cache_file: Some("segments.json".into()),
+        ..Settings::default()
+    };
+    let config = Config::try_from(Settings::defaults().merge(files.clone())).unwrap();
+    assert!(config.domain_stats_file.is_some());
+    assert!(config.resolution_cache_file.is_some());
+
+    let replay = Settings {
+        replay: Some("search.har".into()),
+        ..Settings::default()
+    };
+    let config = Config::try_from(Settings::defaults().merge(files).merge(replay)).unwrap();
+    assert!(config.domain_stats_file.is_none());
+    assert!(config.resolution_cache_file.is_none());
+    assert!(config.segment_cache_file.is_none());
+}
+
+#[test]
+fn test_replay_does_not_learn_domains() {
+    let domains = Settings {
+        domains_file: Some("domains.txt".into()),
+        ..Settings::default()
+    };
+    let config = Config::try_from(Settings::defaults().merge(domains.clone())).unwrap();
+    assert_eq!(config.domains_file, Some("domains.txt".into()));
+
+    let replay = Settings {
+        replay: Some("search.har".into()),
+        ..Settings::default()
+    };
+    let config = Config::try_from(Settings::defaults().merge(domains).merge(replay)).unwrap();
+    assert!(config.domains_file.is_none());
+}
+
+#[tokio::test]
+async fn test_search_against_served_fake_cdn() {
+    let fake_cdn: vods::FakeCdnConfig = toml::from_str(
+        r#"
+        domains = [{ name = "secure" }]
+
+        [[vods]]
+        streamer = "streamer"
+        video-id = "123"
+        start = "2022-09-24 17:02:09"
+        domains = ["secure"]
+        segments = 20
+        muted = ["10..12"]
+        faults = [{ segments = "0..5", fault = { kind = "status", status = 403 } }]
+        "#,
+    )
+    .unwrap();
+    fake_cdn.validate().unwrap();
+    let video_data = fake_cdn.vods[0].video_data().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let domain = format!("http://{}/secure/", listener.local_addr().unwrap());
+    tokio::task::spawn(serve_fake_cdn(Arc::new(fake_cdn), listener, false));
+
+    let found = vods::VodFinder::new(video_data)
+        .domains([domain.clone()])
+        .milliseconds_retry(0)
+        .filter(vods::SegmentFilter::All)
+        .find()
+        .await
+        .unwrap();
+    assert_eq!(found.dwp.domain.as_str(), domain);
+    assert_eq!(found.stats.num_segments, 20);
+    assert_eq!(found.stats.num_valid_segments, 15);
+    assert!(found.playlist.segments[5]
+        .uri
+        .ends_with("/chunked/10-muted.ts"));
+}
@@ -0,0 +1,442 @@
+mod tests;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use bytes::Bytes;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Certificate, Client,
+};
+use tokio::{select, sync::watch};
+
+use crate::{
+    is_connection_error, CancellationToken, Cancelled, Event, EventSink, IgnoreEvents, Limits,
+    ProxyConfig, ProxySelector, RateLimitConfig, Transport, TransportResponse, Watchdog,
+    WatchdogConfig,
+};
+
+pub struct ClientBuildOptions {
+    pub http1_only: bool,
+    pub proxy: Option<String>,
+}
+
+/// Builds the transport of one slot of the pool, usually a `reqwest::Client`.
+pub type ClientFactory =
+    Arc<dyn Fn(&ClientBuildOptions) -> anyhow::Result<Arc<dyn Transport>> + Send + Sync>;
+
+/// A pool of transports (usually `reqwest::Client`s), one per proxy, together with the limits that every request made
+/// through it must respect.
+/// If it has a watchdog, a stalled pool is replaced by freshly built clients
+/// and the requests that were in flight on the old clients are sent again.
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: Arc<Inner>,
+}
+
+struct ClientSlot {
+    proxy: Option<String>,
+    client: Arc<dyn Transport>,
+}
+
+struct ClientPool {
+    generation: u64,
+    slots: Vec<ClientSlot>,
+}
+
+struct Inner {
+    pool: RwLock<ClientPool>,
+    limits: Limits,
+    proxy: ProxySelector,
+    request_counter: AtomicUsize,
+    factory: Option<ClientFactory>,
+    watchdog: Option<Watchdog>,
+    generation: watch::Sender<u64>,
+    observations: Mutex<HashMap<String, HostObservation>>,
+    events: RwLock<Arc<dyn EventSink>>,
+    cancellation: RwLock<Option<CancellationToken>>,
+}
+
+/// What the client saw of one host, e.g. `https://vod-secure.twitch.tv/`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostObservation {
+    pub requests: u64,
+    pub responses: u64,
+    pub connection_failures: u64,
+    /// Summed over every response.
+    pub total_latency: Duration,
+}
+
+impl HostObservation {
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.responses == 0 {
+            None
+        } else {
+            Some(self.total_latency / self.responses as u32)
+        }
+    }
+}
+
+/// How many times one request is sent again because the client was rebuilt underneath it.
+const MAX_REISSUES: usize = 3;
+
+impl HttpClient {
+    pub fn new(client: Client, rate_limit: &RateLimitConfig) -> Self {
+        Self::with_transport(Arc::new(client), rate_limit)
+    }
+
+    /// Sends every request through `transport`, e.g. a wrapped client or a `FakeCdn`.
+    /// There is no watchdog since the transport can't be rebuilt.
+    pub fn with_transport(transport: Arc<dyn Transport>, rate_limit: &RateLimitConfig) -> Self {
+        let slots = vec![ClientSlot {
+            proxy: None,
+            client: transport,
+        }];
+        Self::from_parts(slots, rate_limit, ProxySelector::default(), None, None)
+    }
+
+    /// Builds one client per proxy (or a single direct client if there are no proxies).
+    /// Must be called from within a tokio runtime since the watchdog runs as a background task.
+    pub fn from_factory(
+        factory: ClientFactory,
+        rate_limit: &RateLimitConfig,
+        watchdog: WatchdogConfig,
+        proxy: ProxyConfig,
+    ) -> anyhow::Result<Self> {
+        let proxy = ProxySelector::new(&proxy);
+        let slots = build_slots(factory.as_ref(), &proxy, false)?;
+        let check_interval = watchdog.stall_timeout / 4;
+        let http_client = Self::from_parts(
+            slots,
+            rate_limit,
+            proxy,
+            Some(factory),
+            Some(Watchdog::new(watchdog)),
+        );
+        tokio::task::spawn(watch_for_stalls(
+            Arc::downgrade(&http_client.inner),
+            check_interval.max(Duration::from_millis(10)),
+        ));
+        Ok(http_client)
+    }
+
+    fn from_parts(
+        slots: Vec<ClientSlot>,
+        rate_limit: &RateLimitConfig,
+        proxy: ProxySelector,
+        factory: Option<ClientFactory>,
+        watchdog: Option<Watchdog>,
+    ) -> Self {
+        let (generation, _) = watch::channel(0);
+        Self {
+            inner: Arc::new(Inner {
+                pool: RwLock::new(ClientPool {
+                    generation: 0,
+                    slots,
+                }),
+                limits: Limits::new(rate_limit),
+                proxy,
+                request_counter: AtomicUsize::new(0),
+                factory,
+                watchdog,
+                generation,
+                observations: Mutex::new(HashMap::new()),
+                events: RwLock::new(Arc::new(IgnoreEvents)),
+                cancellation: RwLock::new(None),
+            }),
+        }
+    }
+
+    /// Fails with `Cancelled` as soon as the cancellation token, if any, is cancelled.
+    pub async fn get(&self, url: &str) -> anyhow::Result<TransportResponse> {
+        let token = self.inner.cancellation.read().unwrap().clone();
+        let Some(token) = token else {
+            return self.get_helper(url).await;
+        };
+        if token.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        select! {
+            result = self.get_helper(url) => result,
+            _ = token.cancelled() => Err(Cancelled.into()),
+        }
+    }
+
+    async fn get_helper(&self, url: &str) -> anyhow::Result<TransportResponse> {
+        self.inner.limits.spend_request()?;
+        let mut reissues = 0;
+        loop {
+            self.inner.limits.before_send().await;
+            let (generation, client, proxy) = self.inner.client_for(url);
+            let mut generation_receiver = self.inner.generation.subscribe();
+            let request = self
+                .inner
+                .watchdog
+                .as_ref()
+                .map(|watchdog| watchdog.request_started());
+            let start = Instant::now();
+            let result = select! {
+                result = client.get(url) => Some(result),
+                _ = generation_receiver.wait_for(|current| *current != generation) => None,
+            };
+            let is_connection_error = match &result {
+                Some(Err(err)) => is_connection_error(err),
+                _ => false,
+            };
+            if result.is_some() {
+                self.inner
+                    .observe(url, start.elapsed(), &result, is_connection_error);
+            }
+            if let Some(request) = request {
+                if request.finish(is_connection_error) {
+                    self.inner.rebuild(generation, "too many connection errors");
+                }
+            }
+            let rebuilt = self.inner.generation() != generation;
+            match result {
+                Some(Ok(response)) => return Ok(response),
+                Some(Err(err)) if !(rebuilt && is_connection_error) || reissues >= MAX_REISSUES => {
+                    return Err(match proxy {
+                        Some(proxy) => err.context(format!("via proxy {}", proxy)),
+                        None => err,
+                    })
+                }
+                None if reissues >= MAX_REISSUES => {
+                    return Err(anyhow::anyhow!("request interrupted by client rebuild"))
+                }
+                // the request was on a client that has since been replaced, so send it again
+                _ => reissues += 1,
+            }
+        }
+    }
+
+    /// Reads the whole body of `response`, counting it towards the byte rate limit.
+    /// Responses whose bodies are never read, like those of segment checks, don't count.
+    pub async fn bytes(&self, response: TransportResponse) -> anyhow::Result<Bytes> {
+        let url = response.url().to_string();
+        let bytes = response.bytes().await?;
+        self.inner.limits.after_read(bytes.len()).await;
+        self.emit(Event::BytesDownloaded {
+            url,
+            bytes: bytes.len(),
+        });
+        Ok(bytes)
+    }
+
+    /// Sends the events of this client, and of every clone of it, to `sink`.
+    pub fn set_event_sink(&self, sink: Arc<dyn EventSink>) {
+        *self.inner.events.write().unwrap() = sink;
+    }
+
+    /// Makes every request of this client, and of every clone of it, stop once `token` is
+    /// cancelled.
+    pub fn set_cancellation_token(&self, token: CancellationToken) {
+        *self.inner.cancellation.write().unwrap() = Some(token);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        let token = self.inner.cancellation.read().unwrap();
+        token.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    pub fn emit(&self, event: Event) {
+        self.inner.emit(event);
+    }
+
+    pub fn budget_exhausted(&self) -> bool {
+        self.inner
+            .limits
+            .budget()
+            .map(|budget| budget.is_exhausted())
+            .unwrap_or(false)
+    }
+
+    pub fn requests_spent(&self) -> Option<u64> {
+        self.inner.limits.budget().map(|budget| budget.spent())
+    }
+
+    /// The number of times the client has been rebuilt by the watchdog.
+    pub fn rebuild_count(&self) -> u64 {
+        self.inner.generation()
+    }
+
+    /// Latencies and failures per host for every request made so far.
+    pub fn host_observations(&self) -> HashMap<String, HostObservation> {
+        self.inner.observations.lock().unwrap().clone()
+    }
+
+    /// The proxy that requests to `url` go through.
+    /// Returns `None` for direct requests and for requests whose proxy rotates.
+    pub fn proxy_for(&self, url: &str) -> Option<String> {
+        let index = self.inner.proxy.select_fixed(url)?;
+        let pool = self.inner.pool.read().unwrap();
+        pool.slots.get(index).and_then(|slot| slot.proxy.clone())
+    }
+}
+
+/// The timeouts of `default_client`. The CLI uses them too unless they are configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_millis(1000);
+pub const DEFAULT_HTTP2_KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A client with the default timeouts, for callers that don't build their own.
+/// Without a timeout, a request to a host that stopped answering never finishes.
+pub fn default_client() -> reqwest::Result<Client> {
+    Client::builder()
+        .timeout(DEFAULT_TIMEOUT)
+        .http2_keep_alive_timeout(DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT)
+        .http2_keep_alive_interval(DEFAULT_HTTP2_KEEP_ALIVE_INTERVAL)
+        .http2_keep_alive_while_idle(true)
+        .http2_adaptive_window(true)
+        .use_rustls_tls()
+        .trust_dns(true)
+        .build()
+}
+
+/// Parses a header in the form `Name: value`.
+pub fn parse_header(header: &str) -> anyhow::Result<(HeaderName, HeaderValue)> {
+    let (name, value) = header
+        .split_once(':')
+        .context("header must look like 'Name: value'")?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())?;
+    let value = HeaderValue::from_str(value.trim())?;
+    Ok((name, value))
+}
+
+/// Loads every certificate from a PEM file, or a single certificate from a DER file.
+pub fn load_root_certificate(path: &std::path::Path) -> anyhow::Result<Certificate> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("failed to read certificate {}", path.display()))?;
+    let certificate = if contents.starts_with(b"-----BEGIN") {
+        Certificate::from_pem(&contents)
+    } else {
+        Certificate::from_der(&contents)
+    };
+    certificate.with_context(|| format!("invalid certificate {}", path.display()))
+}
+
+/// Builds one slot per proxy, followed by a direct slot if there is no general proxy pool.
+fn build_slots(
+    factory: &(dyn Fn(&ClientBuildOptions) -> anyhow::Result<Arc<dyn Transport>> + Send + Sync),
+    proxy: &ProxySelector,
+    http1_only: bool,
+) -> anyhow::Result<Vec<ClientSlot>> {
+    let mut proxies = proxy
+        .all_proxies()
+        .iter()
+        .cloned()
+        .map(Some)
+        .collect::<Vec<_>>();
+    if !proxy.has_pool() {
+        proxies.push(None);
+    }
+    proxies
+        .into_iter()
+        .map(|proxy| {
+            let client = factory(&ClientBuildOptions {
+                http1_only,
+                proxy: proxy.clone(),
+            })?;
+            Ok(ClientSlot { proxy, client })
+        })
+        .collect()
+}
+
+impl Inner {
+    fn emit(&self, event: Event) {
+        let sink = Arc::clone(&self.events.read().unwrap());
+        sink.on_event(&event);
+    }
+
+    fn observe(
+        &self,
+        url: &str,
+        latency: Duration,
+        result: &Option<anyhow::Result<TransportResponse>>,
+        is_connection_error: bool,
+    ) {
+        let Ok(parsed) = url::Url::parse(url) else {
+            return;
+        };
+        let host = format!("{}/", &parsed[..url::Position::BeforePath]);
+        let mut observations = self.observations.lock().unwrap();
+        let observation = observations.entry(host).or_default();
+        observation.requests += 1;
+        if is_connection_error {
+            observation.connection_failures += 1;
+        } else if let Some(Ok(_)) = result {
+            observation.responses += 1;
+            observation.total_latency += latency;
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.pool.read().unwrap().generation
+    }
+
+    fn client_for(&self, url: &str) -> (u64, Arc<dyn Transport>, Option<String>) {
+        let counter = self.request_counter.fetch_add(1, Ordering::Relaxed);
+        let pool = self.pool.read().unwrap();
+        // without a match the request goes through the trailing direct slot
+        let index = self
+            .proxy
+            .select(url, counter)
+            .unwrap_or(pool.slots.len() - 1);
+        let slot = &pool.slots[index];
+        (
+            pool.generation,
+            Arc::clone(&slot.client),
+            slot.proxy.clone(),
+        )
+    }
+
+    /// Replaces the client unless somebody else already replaced the `generation` client.
+    fn rebuild(&self, generation: u64, reason: &str) {
+        let (Some(factory), Some(watchdog)) = (&self.factory, &self.watchdog) else {
+            return;
+        };
+        let mut pool = self.pool.write().unwrap();
+        if pool.generation != generation {
+            return;
+        }
+        let http1_only = watchdog.config.fall_back_to_http1;
+        match build_slots(factory.as_ref(), &self.proxy, http1_only) {
+            Ok(slots) => {
+                self.emit(Event::ClientRebuilt {
+                    reason: reason.to_string(),
+                    http1_only,
+                });
+                pool.slots = slots;
+                pool.generation += 1;
+                watchdog.reset();
+                self.generation.send_replace(pool.generation);
+            }
+            Err(err) => self.emit(Event::ClientRebuildFailed {
+                error: err.to_string(),
+            }),
+        }
+    }
+}
+
+async fn watch_for_stalls(inner: Weak<Inner>, check_interval: Duration) {
+    loop {
+        tokio::time::sleep(check_interval).await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let Some(watchdog) = &inner.watchdog else {
+            return;
+        };
+        if watchdog.is_stalled() {
+            let generation = inner.generation();
+            inner.rebuild(generation, "no request completed recently");
+        }
+    }
+}
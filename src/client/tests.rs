@@ -0,0 +1,152 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::testing::{serve_http, HangingTransport};
+#[cfg(test)]
+use crate::{FakeCdn, FakeResponse, ProxyRotation};
+
+/// Starts a stand-in HTTP proxy that answers every request with its own name
+/// and sends the request line it received over the returned channel.
+#[cfg(test)]
+async fn start_fake_proxy(
+    name: &'static str,
+) -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let address = serve_http(move |request_line| {
+        let _ = sender.send(request_line.to_string());
+        (200, name.to_string())
+    })
+    .await;
+    (address, receiver)
+}
+
+#[cfg(test)]
+fn proxied_client(proxy: ProxyConfig) -> HttpClient {
+    HttpClient::from_factory(
+        Arc::new(|options: &ClientBuildOptions| {
+            let mut builder = Client::builder();
+            if let Some(proxy) = &options.proxy {
+                builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+            }
+            let client: Arc<dyn Transport> = Arc::new(builder.build()?);
+            Ok(client)
+        }),
+        &RateLimitConfig::default(),
+        WatchdogConfig::default(),
+        proxy,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_go_through_proxy() {
+    let (proxy, mut requests) = start_fake_proxy("proxy").await;
+    let client = proxied_client(ProxyConfig {
+        proxies: vec![proxy],
+        ..ProxyConfig::default()
+    });
+    let response = client
+        .get("http://vods.invalid/index-dvr.m3u8")
+        .await
+        .unwrap();
+    assert_eq!(client.bytes(response).await.unwrap(), "proxy");
+    assert_eq!(
+        requests.recv().await.unwrap(),
+        "GET http://vods.invalid/index-dvr.m3u8 HTTP/1.1"
+    );
+}
+
+#[tokio::test]
+async fn test_proxy_rotation_and_assignment() {
+    let (first, _first_requests) = start_fake_proxy("first").await;
+    let (second, _second_requests) = start_fake_proxy("second").await;
+    let (assigned, _assigned_requests) = start_fake_proxy("assigned").await;
+    let client = proxied_client(ProxyConfig {
+        proxies: vec![first, second],
+        rotation: ProxyRotation::PerRequest,
+        assignments: vec![("http://special.invalid/".to_string(), assigned.clone())],
+    });
+    let mut bodies = vec![];
+    for _ in 0..2 {
+        let response = client.get("http://vods.invalid/").await.unwrap();
+        bodies.push(client.bytes(response).await.unwrap());
+    }
+    assert_eq!(bodies, vec!["first", "second"]);
+    let response = client.get("http://special.invalid/").await.unwrap();
+    assert_eq!(client.bytes(response).await.unwrap(), "assigned");
+    assert_eq!(client.proxy_for("http://special.invalid/x"), Some(assigned));
+    assert_eq!(client.proxy_for("http://vods.invalid/x"), None);
+}
+
+#[test]
+fn test_per_domain_rotation_is_stable() {
+    let proxy = ProxyConfig {
+        proxies: vec!["http://a:1".to_string(), "http://b:1".to_string()],
+        rotation: ProxyRotation::PerDomain,
+        assignments: vec![],
+    };
+    let proxy = ProxySelector::new(&proxy);
+    let first = proxy.select("https://vod-secure.twitch.tv/x", 0);
+    for counter in 1..10 {
+        assert_eq!(
+            proxy.select("https://vod-secure.twitch.tv/y", counter),
+            first
+        );
+    }
+}
+
+#[test]
+fn test_parse_header() {
+    let (name, value) = parse_header("X-Forwarded-For:  10.0.0.1 ").unwrap();
+    assert_eq!(name, "x-forwarded-for");
+    assert_eq!(value, "10.0.0.1");
+    assert!(parse_header("no colon").is_err());
+    assert!(parse_header("bad name: value").is_err());
+}
+
+#[tokio::test]
+async fn test_reissued_request_spends_budget_once() {
+    let cdn = FakeCdn::new();
+    cdn.insert("http://cdn.example/0.ts", FakeResponse::ConnectionFailed);
+    let transport: Arc<dyn Transport> = cdn.clone();
+    let client = HttpClient::from_factory(
+        Arc::new(move |_: &ClientBuildOptions| Ok(Arc::clone(&transport))),
+        &RateLimitConfig {
+            max_requests: Some(10),
+            ..RateLimitConfig::default()
+        },
+        WatchdogConfig {
+            max_consecutive_connection_errors: 1,
+            ..WatchdogConfig::default()
+        },
+        ProxyConfig::default(),
+    )
+    .unwrap();
+    assert!(client.get("http://cdn.example/0.ts").await.is_err());
+    assert_eq!(cdn.requests().len(), MAX_REISSUES + 1);
+    assert_eq!(client.requests_spent(), Some(1));
+}
+
+#[tokio::test]
+async fn test_dropped_request_does_not_stall_the_client() {
+    let client = HttpClient::from_factory(
+        Arc::new(|_: &ClientBuildOptions| {
+            let transport: Arc<dyn Transport> = Arc::new(HangingTransport);
+            Ok(transport)
+        }),
+        &RateLimitConfig::default(),
+        WatchdogConfig {
+            stall_timeout: Duration::from_millis(50),
+            ..WatchdogConfig::default()
+        },
+        ProxyConfig::default(),
+    )
+    .unwrap();
+    let request = tokio::time::timeout(
+        Duration::from_millis(10),
+        client.get("http://cdn.example/0.ts"),
+    );
+    assert!(request.await.is_err());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(client.rebuild_count(), 0);
+}
@@ -0,0 +1,191 @@
+mod tests;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use crate::ConnectionFailed;
+
+/// Settings for the additive-increase/multiplicative-decrease (AIMD) concurrency controller.
+#[derive(Clone, Debug)]
+pub struct AimdConfig {
+    pub initial: usize,
+    pub min: usize,
+    pub max: usize,
+    /// The limit is multiplied by this on a timeout, reset, 429 or 503.
+    pub decrease_factor: f64,
+    /// Responses slower than this don't raise the limit.
+    pub latency_threshold: Duration,
+    /// A smoothed error rate above this is treated like an overload signal.
+    pub error_rate_threshold: f64,
+    /// Minimum time between two decreases so that one burst of failures only backs off once.
+    pub decrease_cooldown: Duration,
+}
+
+impl Default for AimdConfig {
+    fn default() -> Self {
+        Self {
+            initial: 50,
+            min: 5,
+            max: 200,
+            decrease_factor: 0.5,
+            latency_threshold: Duration::from_millis(1500),
+            error_rate_threshold: 0.2,
+            decrease_cooldown: Duration::from_millis(1000),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestOutcome {
+    /// The server answered with a status that says nothing about load, e.g. 200, 403 or 404.
+    Success { latency: Duration },
+    /// A timeout, a connection reset, a 429 or a 503.
+    Overloaded,
+}
+
+impl RequestOutcome {
+    pub fn from_status(status_code: u16, latency: Duration) -> Self {
+        match status_code {
+            429 | 503 => RequestOutcome::Overloaded,
+            _ => RequestOutcome::Success { latency },
+        }
+    }
+
+    pub fn from_error(err: &reqwest::Error) -> Self {
+        match err.status() {
+            Some(status) => Self::from_status(status.as_u16(), Duration::ZERO),
+            None => RequestOutcome::Overloaded,
+        }
+    }
+
+    /// Errors that don't come from the transport don't say anything about the server's load.
+    pub fn from_anyhow_error(err: &anyhow::Error) -> Self {
+        match err.downcast_ref::<reqwest::Error>() {
+            Some(err) => Self::from_error(err),
+            None if err.is::<ConnectionFailed>() => RequestOutcome::Overloaded,
+            None => RequestOutcome::Success {
+                latency: Duration::ZERO,
+            },
+        }
+    }
+}
+
+struct State {
+    limit: f64,
+    in_flight: usize,
+    error_rate: f64,
+    last_decrease: Option<Instant>,
+}
+
+/// Limits the number of requests in flight and adjusts that limit from the observed outcomes.
+/// The limit grows by roughly one per window of healthy responses and is cut sharply on overload.
+pub struct AimdController {
+    config: AimdConfig,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+/// Holds one of the controller's slots until it is finished or dropped.
+pub struct ConcurrencyPermit {
+    controller: Arc<AimdController>,
+    finished: bool,
+}
+
+const ERROR_RATE_SMOOTHING: f64 = 0.1;
+
+impl AimdController {
+    pub fn new(config: AimdConfig) -> Arc<Self> {
+        let min = config.min.max(1);
+        let max = config.max.max(min);
+        let config = AimdConfig { min, max, ..config };
+        let initial = config.initial.clamp(min, max);
+        Arc::new(Self {
+            config,
+            state: Mutex::new(State {
+                limit: initial as f64,
+                in_flight: 0,
+                error_rate: 0.0,
+                last_decrease: None,
+            }),
+            notify: Notify::new(),
+        })
+    }
+
+    /// The upper bound on the number of permits that can be held at once.
+    pub fn max(&self) -> usize {
+        self.config.max
+    }
+
+    /// The current concurrency limit.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    pub async fn acquire(self: &Arc<Self>) -> ConcurrencyPermit {
+        loop {
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return ConcurrencyPermit {
+                        controller: Arc::clone(self),
+                        finished: false,
+                    };
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn record(&self, outcome: RequestOutcome) {
+        let mut state = self.state.lock().unwrap();
+        let is_error = outcome == RequestOutcome::Overloaded;
+        state.error_rate = state.error_rate * (1.0 - ERROR_RATE_SMOOTHING)
+            + if is_error { ERROR_RATE_SMOOTHING } else { 0.0 };
+        let overloaded = is_error || state.error_rate > self.config.error_rate_threshold;
+        if overloaded {
+            let now = Instant::now();
+            let cooled_down = match state.last_decrease {
+                Some(last) => now.duration_since(last) >= self.config.decrease_cooldown,
+                None => true,
+            };
+            if cooled_down {
+                state.limit = (state.limit * self.config.decrease_factor)
+                    .floor()
+                    .max(self.config.min as f64);
+                state.last_decrease = Some(now);
+            }
+        } else if let RequestOutcome::Success { latency } = outcome {
+            if latency <= self.config.latency_threshold {
+                state.limit = (state.limit + 1.0 / state.limit).min(self.config.max as f64);
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().in_flight -= 1;
+        self.notify.notify_waiters();
+    }
+}
+
+impl ConcurrencyPermit {
+    /// Releases the slot and feeds the outcome of the request back into the controller.
+    pub fn finish(mut self, outcome: RequestOutcome) {
+        self.finished = true;
+        self.controller.record(outcome);
+        self.controller.release();
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.controller.release();
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+use super::*;
+
+#[cfg(test)]
+fn test_config() -> AimdConfig {
+    AimdConfig {
+        initial: 10,
+        min: 2,
+        max: 12,
+        decrease_cooldown: Duration::ZERO,
+        ..AimdConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_aimd_increases_on_healthy_responses() {
+    let controller = AimdController::new(test_config());
+    for _ in 0..100 {
+        let permit = controller.acquire().await;
+        permit.finish(RequestOutcome::Success {
+            latency: Duration::from_millis(10),
+        });
+    }
+    assert_eq!(controller.limit(), 12);
+}
+
+#[tokio::test]
+async fn test_aimd_ignores_slow_responses() {
+    let controller = AimdController::new(test_config());
+    for _ in 0..100 {
+        let permit = controller.acquire().await;
+        permit.finish(RequestOutcome::Success {
+            latency: Duration::from_secs(10),
+        });
+    }
+    assert_eq!(controller.limit(), 10);
+}
+
+#[tokio::test]
+async fn test_aimd_backs_off_on_overload() {
+    let controller = AimdController::new(test_config());
+    controller
+        .acquire()
+        .await
+        .finish(RequestOutcome::Overloaded);
+    assert_eq!(controller.limit(), 5);
+    controller
+        .acquire()
+        .await
+        .finish(RequestOutcome::Overloaded);
+    controller
+        .acquire()
+        .await
+        .finish(RequestOutcome::Overloaded);
+    assert_eq!(controller.limit(), 2);
+}
+
+#[test]
+fn test_request_outcome_from_status() {
+    let latency = Duration::from_millis(5);
+    assert_eq!(
+        RequestOutcome::from_status(404, latency),
+        RequestOutcome::Success { latency }
+    );
+    assert_eq!(
+        RequestOutcome::from_status(429, latency),
+        RequestOutcome::Overloaded
+    );
+    assert_eq!(
+        RequestOutcome::from_status(503, latency),
+        RequestOutcome::Overloaded
+    );
+}
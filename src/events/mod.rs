@@ -0,0 +1,98 @@
+mod tests;
+
+use std::time::Duration;
+
+use crate::AvailabilityEstimate;
+
+/// Something that happened while searching for a VOD or checking its segments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A domain didn't answer the preflight request and won't be searched.
+    DomainUnreachable {
+        domain: String,
+        error: String,
+    },
+    /// A url that needs no search was fetched: the path of a VOD url, or one that a past search
+    /// found if `cached` is set. If `error` is set it didn't work and the domains are searched.
+    KnownUrlChecked {
+        url: String,
+        cached: bool,
+        error: Option<String>,
+    },
+    /// The playlist that a past search fetched from `url` was used without a request, since it
+    /// is younger than the TTL of the resolution cache.
+    CachedPlaylistUsed {
+        url: String,
+        age: Duration,
+    },
+    /// The playlist at a cached url was fetched again and differs from the cached one.
+    CachedPlaylistChanged {
+        url: String,
+    },
+    /// A playlist url is about to be requested.
+    CandidateTried {
+        url: String,
+    },
+    /// A domain had the playlist, or none of its candidates did.
+    /// Domains that were still being probed when another domain won don't report a result.
+    DomainResult {
+        domain: String,
+        found: bool,
+    },
+    /// The VOD's playlist was found. `proxy` is the proxy that its domain is fixed to, if any.
+    UrlFound {
+        url: String,
+        proxy: Option<String>,
+    },
+    /// A sample of the segments was checked. If `escalating` is set, every segment is checked
+    /// next because the sample found some valid and some invalid segments.
+    SampleEstimated {
+        estimate: AvailabilityEstimate,
+        escalating: bool,
+    },
+    /// Segments whose cached check was used instead of a request.
+    SegmentsCached {
+        count: usize,
+        total: usize,
+    },
+    /// One segment was checked. `done` counts the cached segments too.
+    SegmentChecked {
+        index: usize,
+        valid: bool,
+        done: usize,
+        total: usize,
+        concurrency: usize,
+    },
+    /// A response body was read.
+    BytesDownloaded {
+        url: String,
+        bytes: usize,
+    },
+    /// The watchdog replaced a stalled client.
+    ClientRebuilt {
+        reason: String,
+        http1_only: bool,
+    },
+    ClientRebuildFailed {
+        error: String,
+    },
+}
+
+/// Receives the events of an `HttpClient` and of the functions that use it.
+/// Events can come from several tasks at once, so `on_event` should return quickly.
+pub trait EventSink: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+/// Ignores every event. A client reports to this unless it is given another sink.
+pub struct IgnoreEvents;
+
+impl EventSink for IgnoreEvents {
+    fn on_event(&self, _event: &Event) {}
+}
+
+impl<F: Fn(&Event) + Send + Sync> EventSink for F {
+    fn on_event(&self, event: &Event) {
+        self(event)
+    }
+}
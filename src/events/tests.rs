@@ -0,0 +1,26 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::{HttpClient, RateLimitConfig};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn test_client_reports_downloaded_bytes() {
+    let url = "https://cdn.invalid/".to_string();
+    let cdn = crate::FakeCdn::new();
+    cdn.serve(url.clone(), "hello");
+    let events = Arc::new(Mutex::new(vec![]));
+    let client = HttpClient::with_transport(cdn, &RateLimitConfig::default());
+    client.set_event_sink(Arc::new({
+        let events = Arc::clone(&events);
+        move |event: &Event| events.lock().unwrap().push(event.clone())
+    }));
+    // clones share the sink
+    let response = client.clone().get(&url).await.unwrap();
+    client.bytes(response).await.unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![Event::BytesDownloaded { url, bytes: 5 }]
+    );
+}
@@ -0,0 +1,340 @@
+mod tests;
+
+use std::{ops::Range, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::VideoData;
+
+/// The VODs and faults served by the `fake-cdn` command, usually read from a TOML file.
+///
+/// Every domain is served under its name, e.g. `http://127.0.0.1:8080/secure/`,
+/// so that several CDN domains can share one server.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FakeCdnConfig {
+    pub domains: Vec<FakeDomain>,
+    pub vods: Vec<FakeVod>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FakeDomain {
+    pub name: String,
+    /// Applies to every request to the domain, e.g. to simulate a domain that is down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault: Option<Fault>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FakeVod {
+    pub streamer: String,
+    pub video_id: String,
+    /// UTC start time in the format '2006-01-02 15:04:05'
+    pub start: String,
+    /// The names of the domains that hold the VOD.
+    pub domains: Vec<String>,
+    #[serde(default = "FakeVod::default_segments")]
+    pub segments: usize,
+    #[serde(default = "FakeVod::default_segment_duration")]
+    pub segment_duration: f32,
+    /// Whether the path is built from the unix time rather than the second of the start time.
+    #[serde(default = "FakeVod::default_unix_path")]
+    pub unix_path: bool,
+    /// Segments that are listed as `N-unmuted.ts` but are only served as `N-muted.ts`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub muted: Vec<SegmentRange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub faults: Vec<SegmentFault>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SegmentFault {
+    pub segments: SegmentRange,
+    pub fault: Fault,
+}
+
+/// A range of segment indices written like '40..100'.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SegmentRange(pub Range<usize>);
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Fault {
+    /// Answer with this status code, e.g. 403 or 404.
+    Status { status: u16 },
+    /// Never answer.
+    Timeout,
+    /// Close the connection without answering.
+    Reset,
+    /// Answer with a 503 for this share of the requests, and normally otherwise.
+    Flaky {
+        #[serde(rename = "failure-rate")]
+        failure_rate: f64,
+    },
+}
+
+/// What the server does with one request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FakeReply {
+    Respond { status: u16, body: Bytes },
+    Timeout,
+    Reset,
+}
+
+impl TryFrom<String> for SegmentRange {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (start, end) = value
+            .split_once("..")
+            .with_context(|| format!("segment range '{}' must look like '40..100'", value))?;
+        Ok(SegmentRange(start.trim().parse()?..end.trim().parse()?))
+    }
+}
+
+impl SegmentRange {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.0.start > self.0.end {
+            return Err(anyhow!(
+                "segment range '{}..{}' ends before it starts",
+                self.0.start,
+                self.0.end
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<SegmentRange> for String {
+    fn from(range: SegmentRange) -> Self {
+        format!("{}..{}", range.0.start, range.0.end)
+    }
+}
+
+impl FakeVod {
+    fn default_segments() -> usize {
+        100
+    }
+
+    fn default_segment_duration() -> f32 {
+        10.0
+    }
+
+    fn default_unix_path() -> bool {
+        true
+    }
+
+    pub fn video_data(&self) -> anyhow::Result<VideoData> {
+        Ok(VideoData {
+            streamer_name: Arc::new(self.streamer.clone()),
+            video_id: Arc::new(self.video_id.clone()),
+            unix_time_seconds: NaiveDateTime::parse_from_str(&self.start, "%Y-%m-%d %H:%M:%S")
+                .with_context(|| format!("invalid start time '{}'", self.start))?,
+        })
+    }
+
+    pub fn url_path(&self) -> anyhow::Result<String> {
+        Ok(self.video_data()?.get_url_path(self.unix_path))
+    }
+
+    fn is_muted(&self, index: usize) -> bool {
+        self.muted.iter().any(|range| range.0.contains(&index))
+    }
+
+    fn fault(&self, index: usize) -> Option<&Fault> {
+        self.faults
+            .iter()
+            .find(|fault| fault.segments.0.contains(&index))
+            .map(|fault| &fault.fault)
+    }
+
+    /// The `index-dvr.m3u8` of the VOD, with relative segment urls like Twitch's.
+    pub fn playlist(&self) -> String {
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-PLAYLIST-TYPE:EVENT\n#EXT-X-MEDIA-SEQUENCE:0\n",
+            self.segment_duration.ceil()
+        );
+        for index in 0..self.segments {
+            let suffix = if self.is_muted(index) { "-unmuted" } else { "" };
+            playlist += &format!(
+                "#EXTINF:{:.3},\n{}{}.ts\n",
+                self.segment_duration, index, suffix
+            );
+        }
+        playlist + "#EXT-X-ENDLIST\n"
+    }
+
+    /// Answers a request for `file` in the VOD's `chunked` directory.
+    fn respond(&self, file: &str, rng: &mut impl Rng) -> FakeReply {
+        if file == "index-dvr.m3u8" {
+            return FakeReply::Respond {
+                status: 200,
+                body: self.playlist().into(),
+            };
+        }
+        let Some(name) = file.strip_suffix(".ts") else {
+            return not_found();
+        };
+        let (index, muted) = match name.strip_suffix("-muted") {
+            Some(index) => (index, true),
+            None => (name, false),
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            return not_found();
+        };
+        if index >= self.segments || muted != self.is_muted(index) {
+            return not_found();
+        }
+        if let Some(reply) = self.fault(index).and_then(|fault| fault.apply(rng)) {
+            return reply;
+        }
+        FakeReply::Respond {
+            status: 200,
+            body: format!("fake segment {} of {}\n", index, self.video_id).into(),
+        }
+    }
+}
+
+impl Fault {
+    /// The reply that replaces the normal one, if any.
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Fault::Flaky { failure_rate } if !(0.0..=1.0).contains(failure_rate) => Err(anyhow!(
+                "failure rate {} must be between 0 and 1",
+                failure_rate
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, rng: &mut impl Rng) -> Option<FakeReply> {
+        match self {
+            Fault::Status { status } => Some(FakeReply::Respond {
+                status: *status,
+                body: Bytes::new(),
+            }),
+            Fault::Timeout => Some(FakeReply::Timeout),
+            Fault::Reset => Some(FakeReply::Reset),
+            Fault::Flaky { failure_rate } => {
+                rng.gen_bool(failure_rate.clamp(0.0, 1.0))
+                    .then(|| FakeReply::Respond {
+                        status: 503,
+                        body: Bytes::new(),
+                    })
+            }
+        }
+    }
+}
+
+impl FakeCdnConfig {
+    /// Two domains with a VOD whose start was deleted, some muted segments and flaky segments.
+    pub fn example() -> Self {
+        FakeCdnConfig {
+            domains: vec![
+                FakeDomain {
+                    name: "secure".to_string(),
+                    fault: None,
+                },
+                FakeDomain {
+                    name: "down".to_string(),
+                    fault: Some(Fault::Timeout),
+                },
+            ],
+            vods: vec![FakeVod {
+                streamer: "streamer".to_string(),
+                video_id: "123".to_string(),
+                start: "2022-09-24 17:02:09".to_string(),
+                domains: vec!["secure".to_string()],
+                segments: 500,
+                segment_duration: 10.0,
+                unix_path: true,
+                muted: vec![SegmentRange(100..120)],
+                faults: vec![
+                    SegmentFault {
+                        segments: SegmentRange(0..40),
+                        fault: Fault::Status { status: 403 },
+                    },
+                    SegmentFault {
+                        segments: SegmentRange(300..310),
+                        fault: Fault::Flaky { failure_rate: 0.5 },
+                    },
+                ],
+            }],
+        }
+    }
+
+    /// Checks that every VOD has a valid start time and only refers to known domains, that
+    /// every segment range ends after it starts, and that failure rates are between 0 and 1.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for domain in &self.domains {
+            if let Some(fault) = &domain.fault {
+                fault
+                    .validate()
+                    .with_context(|| format!("invalid fault of domain '{}'", domain.name))?;
+            }
+        }
+        for vod in &self.vods {
+            vod.video_data()?;
+            let context = || format!("invalid VOD {}", vod.video_id);
+            for range in &vod.muted {
+                range.validate().with_context(context)?;
+            }
+            for fault in &vod.faults {
+                fault.segments.validate().with_context(context)?;
+                fault.fault.validate().with_context(context)?;
+            }
+            for domain in &vod.domains {
+                if !self.domains.iter().any(|known| &known.name == domain) {
+                    return Err(anyhow!(
+                        "VOD {} is on unknown domain '{}'",
+                        vod.video_id,
+                        domain
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers a request for `path`, e.g. `/secure/{url_path}/chunked/0.ts`.
+    pub fn respond(&self, path: &str, rng: &mut impl Rng) -> FakeReply {
+        let path = path.split('?').next().unwrap_or(path);
+        let mut parts = path.trim_start_matches('/').splitn(2, '/');
+        let (Some(domain_name), rest) = (parts.next(), parts.next().unwrap_or("")) else {
+            return not_found();
+        };
+        let Some(domain) = self
+            .domains
+            .iter()
+            .find(|domain| domain.name == domain_name)
+        else {
+            return not_found();
+        };
+        if let Some(reply) = domain.fault.as_ref().and_then(|fault| fault.apply(rng)) {
+            return reply;
+        }
+        let Some((url_path, file)) = rest.split_once("/chunked/") else {
+            return not_found();
+        };
+        self.vods
+            .iter()
+            .filter(|vod| vod.domains.iter().any(|name| name == domain_name))
+            .find(|vod| vod.url_path().is_ok_and(|path| path == url_path))
+            .map_or_else(not_found, |vod| vod.respond(file, rng))
+    }
+}
+
+fn not_found() -> FakeReply {
+    FakeReply::Respond {
+        status: 404,
+        body: Bytes::new(),
+    }
+}
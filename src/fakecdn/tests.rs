@@ -0,0 +1,95 @@
+#[cfg(test)]
+use super::*;
+
+#[cfg(test)]
+fn status(reply: FakeReply) -> u16 {
+    match reply {
+        FakeReply::Respond { status, .. } => status,
+        reply => panic!("expected a response, got {:?}", reply),
+    }
+}
+
+#[test]
+fn test_fake_cdn_serves_example_vod() {
+    let config = FakeCdnConfig::example();
+    config.validate().unwrap();
+    let mut rng = rand::thread_rng();
+    let url_path = config.vods[0].url_path().unwrap();
+    let chunked = format!("/secure/{}/chunked/", url_path);
+    let reply = config.respond(&format!("{}index-dvr.m3u8", chunked), &mut rng);
+    let FakeReply::Respond { status: 200, body } = reply else {
+        panic!("no playlist");
+    };
+    let playlist = crate::decode_media_playlist_filter_nil_segments(body).unwrap();
+    assert_eq!(playlist.segments.len(), 500);
+    assert_eq!(playlist.segments[100].uri, "100-unmuted.ts");
+
+    let respond =
+        |file: &str| config.respond(&format!("{}{}", chunked, file), &mut rand::thread_rng());
+    assert_eq!(status(respond("39.ts")), 403);
+    assert_eq!(status(respond("40.ts")), 200);
+    assert_eq!(status(respond("100-unmuted.ts")), 404);
+    assert_eq!(status(respond("100-muted.ts")), 200);
+    assert_eq!(status(respond("120.ts")), 200);
+    assert_eq!(status(respond("500.ts")), 404);
+    // the VOD is only on one domain, and the other one never answers
+    let reply = config.respond(
+        &format!("/down/{}/chunked/index-dvr.m3u8", url_path),
+        &mut rng,
+    );
+    assert_eq!(reply, FakeReply::Timeout);
+    assert_eq!(status(config.respond("/other/", &mut rng)), 404);
+}
+
+#[test]
+fn test_fake_cdn_config_from_toml() {
+    let config: FakeCdnConfig = toml::from_str(
+        r#"
+        domains = [{ name = "a" }, { name = "b", fault = { kind = "reset" } }]
+
+        [[vods]]
+        streamer = "streamer"
+        video-id = "123"
+        start = "2022-09-24 17:02:09"
+        domains = ["a"]
+        unix-path = false
+        faults = [
+          { segments = "5..7", fault = { kind = "status", status = 404 } },
+          { segments = "8..9", fault = { kind = "flaky", failure-rate = 1.0 } },
+        ]
+        "#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.domains[1].fault, Some(Fault::Reset));
+    assert_eq!(config.vods[0].segments, 100);
+    assert_eq!(config.vods[0].faults[0].segments, SegmentRange(5..7));
+    let url_path = config.vods[0].url_path().unwrap();
+    let reply = config.respond(
+        &format!("/a/{}/chunked/8.ts", url_path),
+        &mut rand::thread_rng(),
+    );
+    assert_eq!(status(reply), 503);
+
+    let config: FakeCdnConfig =
+        toml::from_str("[[vods]]\nstreamer = \"s\"\nvideo-id = \"1\"\nstart = \"2022-09-24 17:02:09\"\ndomains = [\"x\"]\n")
+            .unwrap();
+    assert!(config.validate().is_err());
+
+    let with_vod = |extra: &str| -> FakeCdnConfig {
+        toml::from_str(&format!(
+            "domains = [{{ name = \"a\" }}]\n[[vods]]\nstreamer = \"s\"\nvideo-id = \"1\"\nstart = \"2022-09-24 17:02:09\"\ndomains = [\"a\"]\n{}",
+            extra
+        ))
+        .unwrap()
+    };
+    with_vod("").validate().unwrap();
+    assert!(with_vod("muted = [\"7..5\"]").validate().is_err());
+    for rate in ["nan", "inf", "-0.5", "1.5"] {
+        let fault = format!(
+            "faults = [{{ segments = \"0..1\", fault = {{ kind = \"flaky\", failure-rate = {} }} }}]",
+            rate
+        );
+        assert!(with_vod(&fault).validate().is_err(), "{}", rate);
+    }
+}
@@ -0,0 +1,666 @@
+mod tests;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use m3u8_rs::MediaPlaylist;
+
+use crate::{
+    check_indices, default_client, get_first_valid_dwp, get_media_playlist_with_valid_ranges,
+    get_media_playlist_with_valid_segments, preflight_domains, stratified_sample_indices,
+    url_to_domain_with_path, AimdConfig, AvailabilityEstimate, DomainStats, DomainWithPath, Event,
+    HeadStart, HostObservation, HttpClient, RateLimitConfig, ResolutionCache, SegmentCache,
+    SegmentCachePolicy, StreamsChartsData, SullyGnomeData, TransformContext, TransformPipeline,
+    TwitchTrackerData, ValidDwpResponse, VideoData, DEFAULT_RESOLUTION_TTL, DOMAINS,
+};
+
+/// Where the data about a VOD came from.
+pub enum VodSource {
+    TwitchTracker(TwitchTrackerData),
+    StreamsCharts(StreamsChartsData),
+    SullyGnome(SullyGnomeData),
+    /// Any url of the VOD, e.g. a storyboard url. Its path is fetched before anything is
+    /// searched, and its domain is searched first.
+    Url(String),
+}
+
+impl VodSource {
+    /// The number of seconds after the start time that are searched.
+    /// StreamsCharts only gives the start time to the minute.
+    pub fn search_window(&self) -> i64 {
+        match self {
+            VodSource::StreamsCharts(_) => 60,
+            _ => 1,
+        }
+    }
+}
+
+/// What to do with the segments of the playlist that was found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SegmentFilter {
+    /// Keep every segment without checking it.
+    #[default]
+    None,
+    /// Check every segment and drop the invalid ones.
+    All,
+    /// Check every `stride`th segment and bisect in between to find the runs of valid segments.
+    Bisect { stride: usize },
+    /// Check a random sample of `size` segments, spread over the whole VOD, to estimate how many
+    /// are valid. If `escalate` is set and the sample has both valid and invalid segments,
+    /// every segment is checked and the invalid ones are dropped.
+    Sample { size: usize, escalate: bool },
+}
+
+/// Finds the playlist of a VOD: probes the domains for the VOD's path, decodes the playlist,
+/// mutes the segments, makes their urls explicit and optionally drops the invalid ones.
+///
+/// Before probing, the exact path of a `VodSource::Url` and then the url in the resolution cache
+/// are tried. Probing can be preceded by a preflight request to every domain, and the domains
+/// can be ordered by their history. Progress is reported to the client's event sink.
+///
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use vods::{SegmentFilter, TwitchTrackerData, VodFinder, VodSource};
+///
+/// let found = VodFinder::from_source(VodSource::TwitchTracker(TwitchTrackerData {
+///     streamer_name: "gmhikaru".to_string(),
+///     video_id: "47198535725".to_string(),
+///     utc_time: "2022-09-24 17:02:09".to_string(),
+/// }))?
+/// .filter(SegmentFilter::Bisect { stride: 32 })
+/// .find()
+/// .await?;
+/// println!("{}", found.dwp.get_index_dvr_url());
+/// # Ok(())
+/// # }
+/// ```
+pub struct VodFinder {
+    video_data: VideoData,
+    window_seconds: i64,
+    url_domain: Option<Arc<String>>,
+    exact_path: Option<DomainWithPath<Arc<String>>>,
+    domains: Vec<Arc<String>>,
+    client: Option<HttpClient>,
+    milliseconds_retry: u64,
+    head_start: HeadStart,
+    preflight: bool,
+    domain_stats: Option<Arc<Mutex<DomainStats>>>,
+    resolution_cache: Option<ResolutionCache>,
+    resolution_cache_ttl: Duration,
+    filter: SegmentFilter,
+    concurrency: AimdConfig,
+    segment_cache: SegmentCache,
+    segment_cache_policy: SegmentCachePolicy,
+    transforms: TransformPipeline,
+}
+
+/// The result of `VodFinder::find`.
+pub struct FoundVod {
+    pub dwp: DomainWithPath<Arc<String>>,
+    /// The playlist after the transforms. By default its segment urls are muted and explicit,
+    /// and it is filtered according to the `SegmentFilter`.
+    pub playlist: MediaPlaylist,
+    pub stats: FindStats,
+    pub diagnostics: FindDiagnostics,
+    /// The cache passed to `VodFinder::segment_cache`, with the segments checked by this search added.
+    pub segment_cache: SegmentCache,
+    /// The cache passed to `VodFinder::resolution_cache`, with the url that was found added.
+    pub resolution_cache: Option<ResolutionCache>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FindStats {
+    pub search_time: Duration,
+    pub validation_time: Duration,
+    /// The number of segments in the playlist before filtering.
+    pub num_segments: usize,
+    pub num_valid_segments: usize,
+    /// The number of segments that were requested or found in the segment cache.
+    pub num_checked: usize,
+    /// Whether segments were dropped based on their checks. This is false for
+    /// `SegmentFilter::None` and for a sample that wasn't escalated.
+    pub filtered: bool,
+    /// The runs of valid segments, as indices into the unfiltered playlist.
+    /// Only set by `SegmentFilter::Bisect`.
+    pub valid_ranges: Vec<Range<usize>>,
+    /// Where each of `valid_ranges` starts and ends in the VOD.
+    pub valid_offsets: Vec<Range<Duration>>,
+    /// Only set by `SegmentFilter::Sample`.
+    pub estimate: Option<AvailabilityEstimate>,
+}
+
+#[derive(Debug, Default)]
+pub struct FindDiagnostics {
+    /// How many seconds after the given start time the VOD's path was found.
+    /// This is -1 for VODs whose path uses the second before the start time.
+    pub time_offset_seconds: i64,
+    /// Whether the path was built from the unix time rather than the second of the start time.
+    pub unix_time_path: bool,
+    /// How often the HTTP client was rebuilt because requests stalled.
+    pub client_rebuilds: u64,
+    pub hosts: HashMap<String, HostObservation>,
+    /// Set if checking stopped before every segment was checked.
+    /// In that case the playlist only contains the segments found to be valid so far, and the
+    /// estimate is from the sampled segments checked so far.
+    pub validation_error: Option<anyhow::Error>,
+}
+
+impl VodFinder {
+    /// Searches the built-in domains for a VOD that started at most one second after
+    /// `video_data`'s time.
+    pub fn new(video_data: VideoData) -> Self {
+        Self {
+            video_data,
+            window_seconds: 1,
+            url_domain: None,
+            exact_path: None,
+            domains: DOMAINS
+                .iter()
+                .map(|domain| Arc::new(domain.to_string()))
+                .collect(),
+            client: None,
+            milliseconds_retry: 2000,
+            head_start: HeadStart::default(),
+            preflight: false,
+            domain_stats: None,
+            resolution_cache: None,
+            resolution_cache_ttl: DEFAULT_RESOLUTION_TTL,
+            filter: SegmentFilter::default(),
+            concurrency: AimdConfig::default(),
+            segment_cache: SegmentCache::default(),
+            segment_cache_policy: SegmentCachePolicy::default(),
+            transforms: TransformPipeline::default(),
+        }
+    }
+
+    pub fn from_source(source: VodSource) -> anyhow::Result<Self> {
+        let window_seconds = source.search_window();
+        let (video_data, exact_path) = match source {
+            VodSource::TwitchTracker(data) => (data.try_into()?, None),
+            VodSource::StreamsCharts(data) => (data.try_into()?, None),
+            VodSource::SullyGnome(data) => (data.try_into()?, None),
+            VodSource::Url(url) => {
+                let dwp = url_to_domain_with_path(&url)?;
+                (dwp.path.video_data.with_offset(0), Some(dwp))
+            }
+        };
+        let mut finder = Self::new(video_data).window_seconds(window_seconds);
+        if let Some(dwp) = exact_path {
+            finder.url_domain = Some(Arc::clone(&dwp.domain));
+            finder = finder.exact_path(dwp);
+        }
+        Ok(finder)
+    }
+
+    /// The number of seconds after the start time that are searched.
+    pub fn window_seconds(mut self, seconds: i64) -> Self {
+        self.window_seconds = seconds;
+        self
+    }
+
+    /// Replaces the built-in domains. They are probed in the given order.
+    pub fn domains<I, S>(mut self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.domains = domains
+            .into_iter()
+            .map(|domain| Arc::new(domain.into()))
+            .collect();
+        self
+    }
+
+    /// A path that is fetched before anything else, e.g. the one of a VOD url.
+    /// The domains are only searched if it doesn't work.
+    pub fn exact_path(mut self, dwp: DomainWithPath<Arc<String>>) -> Self {
+        self.exact_path = Some(dwp);
+        self
+    }
+
+    /// The client to send every request with.
+    /// It defaults to `default_client` without rate limits.
+    pub fn client(mut self, client: HttpClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn milliseconds_retry(mut self, milliseconds_retry: u64) -> Self {
+        self.milliseconds_retry = milliseconds_retry;
+        self
+    }
+
+    pub fn head_start(mut self, head_start: HeadStart) -> Self {
+        self.head_start = head_start;
+        self
+    }
+
+    /// Send one request to every domain before probing, and skip the domains that don't answer.
+    pub fn preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Probe the domains that held VODs of this age most often first, and record the outcome of
+    /// the search. The stats are shared so that the caller can save them even if `find` fails.
+    pub fn domain_stats(mut self, stats: Arc<Mutex<DomainStats>>) -> Self {
+        self.domain_stats = Some(stats);
+        self
+    }
+
+    /// Use the playlist that a past search with the same window found if it was fetched less
+    /// than `ttl` ago, and otherwise fetch it again from the same url before probing.
+    /// The updated cache is returned in `FoundVod::resolution_cache`.
+    pub fn resolution_cache(mut self, cache: ResolutionCache, ttl: Duration) -> Self {
+        self.resolution_cache = Some(cache);
+        self.resolution_cache_ttl = ttl;
+        self
+    }
+
+    pub fn filter(mut self, filter: SegmentFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: AimdConfig) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Segments with a fresh entry in `cache` aren't requested again.
+    /// The updated cache is returned in `FoundVod::segment_cache`.
+    pub fn segment_cache(mut self, cache: SegmentCache, policy: SegmentCachePolicy) -> Self {
+        self.segment_cache = cache;
+        self.segment_cache_policy = policy;
+        self
+    }
+
+    /// The steps that turn the playlist that was found into `FoundVod::playlist`.
+    /// They are applied after the segments were checked, to the playlist as it was served.
+    pub fn transforms(mut self, transforms: TransformPipeline) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    pub async fn find(mut self) -> anyhow::Result<FoundVod> {
+        let client = match self.client.take() {
+            Some(client) => client,
+            None => HttpClient::new(default_client()?, &RateLimitConfig::default()),
+        };
+        let started = Instant::now();
+        let mut known = match self.exact_path.take() {
+            Some(dwp) => fetch_known_dwp(dwp, false, &client, self.milliseconds_retry).await,
+            None => None,
+        };
+        let mut resolution_cache = self.resolution_cache.take();
+        let now = chrono::Utc::now().timestamp();
+        // set if the cached playlist is used as it is, so that its entry stays as it was
+        let mut served_from_cache = false;
+        if let (None, Some(cache)) = (&known, &resolution_cache) {
+            let cached = cache
+                .get(&self.video_data, self.window_seconds)
+                .and_then(|cached| Some((cached, cached.to_domain_with_path().ok()?)));
+            if let Some((cached, dwp)) = cached {
+                if cached.is_fresh(self.resolution_cache_ttl, now) {
+                    client.emit(Event::CachedPlaylistUsed {
+                        url: dwp.get_index_dvr_url(),
+                        age: Duration::from_secs((now - cached.resolved_at).max(0) as u64),
+                    });
+                    served_from_cache = true;
+                    known = Some(ValidDwpResponse {
+                        dwp,
+                        body: Bytes::from(cached.body.clone()),
+                    });
+                } else {
+                    known = fetch_known_dwp(dwp, true, &client, self.milliseconds_retry).await;
+                    if let Some(found) = &known {
+                        if !cached.body.is_empty() && found.body != cached.body.as_bytes() {
+                            client.emit(Event::CachedPlaylistChanged {
+                                url: found.dwp.get_index_dvr_url(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        let found = match known {
+            Some(found) => found,
+            None => self.search(&client).await?,
+        };
+        let search_time = started.elapsed();
+        if let (false, Some(cache)) = (served_from_cache, &mut resolution_cache) {
+            cache.insert(&self.video_data, self.window_seconds, &found, now);
+        }
+        let url = found.dwp.get_index_dvr_url();
+        client.emit(Event::UrlFound {
+            proxy: client.proxy_for(&url),
+            url,
+        });
+        let found_data = Arc::clone(&found.dwp.path.video_data);
+        let mut playlist = prepare_media_playlist(&found)?;
+        let num_segments = playlist.segments.len();
+        let durations = playlist
+            .segments
+            .iter()
+            .map(|segment| Duration::from_secs_f64(segment.duration as f64))
+            .collect::<Vec<_>>();
+
+        let started = Instant::now();
+        let mut segment_cache = self.segment_cache;
+        let mut num_checked = 0;
+        let mut valid_ranges = vec![];
+        let mut estimate = None;
+        let mut validation_error = None;
+        let mut check_all = self.filter == SegmentFilter::All;
+        let stopped = |num_checked: usize, num_total: usize, what: &str| {
+            format!(
+                "stopped after checking {} {} out of {}",
+                num_checked, what, num_total
+            )
+        };
+        match self.filter {
+            SegmentFilter::None | SegmentFilter::All => {}
+            SegmentFilter::Bisect { stride } => {
+                let response = get_media_playlist_with_valid_ranges(
+                    playlist,
+                    stride,
+                    &self.concurrency,
+                    client.clone(),
+                    self.milliseconds_retry,
+                    &mut segment_cache,
+                    &self.segment_cache_policy,
+                )
+                .await;
+                playlist = response.playlist;
+                num_checked = response.num_checked;
+                valid_ranges = response.ranges;
+                validation_error = response.error.map(|err| {
+                    err.context(stopped(response.num_checked, num_segments, "segments"))
+                });
+            }
+            SegmentFilter::Sample { size, escalate } => {
+                let mut sample = playlist.clone();
+                sample.segments =
+                    stratified_sample_indices(num_segments, size, &mut rand::thread_rng())
+                        .into_iter()
+                        .map(|index| playlist.segments[index].clone())
+                        .collect();
+                let num_sampled = sample.segments.len();
+                let response = get_media_playlist_with_valid_segments(
+                    sample,
+                    &self.concurrency,
+                    client.clone(),
+                    self.milliseconds_retry,
+                    &mut segment_cache,
+                    &self.segment_cache_policy,
+                )
+                .await;
+                let sample_estimate = AvailabilityEstimate::new(
+                    response.playlist.segments.len(),
+                    response.num_checked,
+                    num_segments,
+                );
+                num_checked = response.num_checked;
+                validation_error = response.error.map(|err| {
+                    err.context(stopped(
+                        response.num_checked,
+                        num_sampled,
+                        "sampled segments",
+                    ))
+                });
+                check_all =
+                    escalate && validation_error.is_none() && sample_estimate.is_partial_loss();
+                client.emit(Event::SampleEstimated {
+                    estimate: sample_estimate,
+                    escalating: check_all,
+                });
+                estimate = Some(sample_estimate);
+            }
+        }
+        if check_all {
+            let response = get_media_playlist_with_valid_segments(
+                playlist,
+                &self.concurrency,
+                client.clone(),
+                self.milliseconds_retry,
+                &mut segment_cache,
+                &self.segment_cache_policy,
+            )
+            .await;
+            playlist = response.playlist;
+            num_checked = response.num_checked;
+            validation_error = response
+                .error
+                .map(|err| err.context(stopped(response.num_checked, num_segments, "segments")));
+        }
+        let filtered = check_all || matches!(self.filter, SegmentFilter::Bisect { .. });
+        let mut unmuted = None;
+        if self.transforms.contains("unmute") {
+            let (valid, error) = check_unmuted_segments(
+                &playlist,
+                &self.concurrency,
+                client.clone(),
+                self.milliseconds_retry,
+                &mut segment_cache,
+                &self.segment_cache_policy,
+            )
+            .await;
+            unmuted = Some(valid);
+            validation_error = validation_error.or(error);
+        }
+        let validation_time = started.elapsed();
+        let num_valid_segments = playlist.segments.len();
+        let offset = |index: usize| durations[..index].iter().sum::<Duration>();
+        let valid_offsets = valid_ranges
+            .iter()
+            .map(|range| offset(range.start)..offset(range.end))
+            .collect();
+
+        // the checks ran on the muted, explicit playlist, and the transforms start over from
+        // the playlist as it was served
+        let mut output = crate::decode_media_playlist_filter_nil_segments(found.body.clone())?;
+        let mut context = TransformContext::new(&output, &found.dwp);
+        if filtered {
+            context = context.with_valid_segments(&playlist);
+        }
+        if let Some(unmuted) = &unmuted {
+            context = context.with_unmuted_segments(unmuted.iter().map(String::as_str));
+        }
+        self.transforms.apply(&mut output, &context)?;
+
+        Ok(FoundVod {
+            stats: FindStats {
+                search_time,
+                validation_time,
+                num_segments,
+                num_valid_segments,
+                num_checked,
+                filtered,
+                valid_ranges,
+                valid_offsets,
+                estimate,
+            },
+            diagnostics: FindDiagnostics {
+                time_offset_seconds: (found_data.unix_time_seconds
+                    - self.video_data.unix_time_seconds)
+                    .num_seconds(),
+                unix_time_path: found.dwp.path.url_path == found_data.get_url_path(true),
+                client_rebuilds: client.rebuild_count(),
+                hosts: client.host_observations(),
+                validation_error,
+            },
+            dwp: found.dwp,
+            playlist: output,
+            segment_cache,
+            resolution_cache,
+        })
+    }
+
+    /// Probes the domains for the VOD, after the preflight and ordered by the domain stats if
+    /// they are enabled, and records the outcome in the domain stats.
+    async fn search(&self, client: &HttpClient) -> anyhow::Result<ValidDwpResponse<Arc<String>>> {
+        let now = chrono::Utc::now().naive_utc();
+        let vod_age = now - self.video_data.unix_time_seconds;
+        let mut domains = match &self.domain_stats {
+            Some(stats) => stats.lock().unwrap().rank(&self.domains, vod_age),
+            None => self.domains.clone(),
+        };
+        if let Some(domain) = &self.url_domain {
+            if !domains.contains(domain) {
+                domains.insert(0, Arc::clone(domain));
+            }
+        }
+        if self.preflight {
+            let preflight = preflight_domains(&domains, client).await?;
+            for (domain, err) in &preflight.unreachable {
+                client.emit(Event::DomainUnreachable {
+                    domain: domain.to_string(),
+                    error: format!("{:#}", err),
+                });
+            }
+            if preflight.reachable.is_empty() && !domains.is_empty() {
+                return Err(anyhow!(
+                    "none of the {} domains are reachable",
+                    domains.len()
+                ));
+            }
+            domains = preflight.reachable;
+        }
+        // some m3u8 file names use a time that is 1 second minus the provided time
+        let found = get_valid_dwp(
+            &domains,
+            self.window_seconds + 1,
+            self.video_data.with_offset(-1),
+            client.clone(),
+            self.milliseconds_retry,
+            self.head_start,
+        )
+        .await;
+        // a search that was cut short says nothing about the domains
+        if let Some(stats) = &self.domain_stats {
+            if !client.budget_exhausted() && !client.is_cancelled() {
+                let winner = found.as_ref().ok().map(|found| &found.dwp.domain);
+                let mut stats = stats.lock().unwrap();
+                stats.record_search(&domains, winner, vod_age, now.timestamp());
+                stats.record_observations(&client.host_observations());
+            }
+        }
+        match found {
+            Ok(found) => Ok(found),
+            Err(err) if client.budget_exhausted() => {
+                Err(err.context("request budget exhausted before a valid url was found"))
+            }
+            Err(err) if client.is_cancelled() => {
+                Err(err.context("stopped before a valid url was found"))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Checks the unmuted versions of the muted segments of `playlist`, and returns the urls of
+/// the ones that are valid.
+async fn check_unmuted_segments(
+    playlist: &MediaPlaylist,
+    concurrency: &AimdConfig,
+    client: HttpClient,
+    milliseconds_retry: u64,
+    segment_cache: &mut SegmentCache,
+    policy: &SegmentCachePolicy,
+) -> (Vec<String>, Option<anyhow::Error>) {
+    let urls = playlist
+        .segments
+        .iter()
+        .filter_map(|segment| segment.uri.strip_suffix("-muted.ts"))
+        .map(|prefix| format!("{}-unmuted.ts", prefix))
+        .collect::<Vec<_>>();
+    let indices = (0..urls.len()).collect();
+    let urls = Arc::new(urls);
+    let (checks, error) = check_indices(
+        &urls,
+        indices,
+        concurrency,
+        client,
+        milliseconds_retry,
+        segment_cache,
+        policy,
+    )
+    .await;
+    let valid = checks
+        .into_iter()
+        .filter(|(_, check)| check.is_valid())
+        .map(|(index, _)| urls[index].clone())
+        .collect();
+    (
+        valid,
+        error.map(|err| err.context("stopped checking the unmuted segments")),
+    )
+}
+
+/// Fetches the playlist at a path that needs no search, and reports whether it worked.
+async fn fetch_known_dwp(
+    dwp: DomainWithPath<Arc<String>>,
+    cached: bool,
+    client: &HttpClient,
+    milliseconds_retry: u64,
+) -> Option<ValidDwpResponse<Arc<String>>> {
+    let result = dwp.get_m3u8_body(client.clone(), milliseconds_retry).await;
+    client.emit(Event::KnownUrlChecked {
+        url: dwp.get_index_dvr_url(),
+        cached,
+        error: result.as_ref().err().map(|err| format!("{:#}", err)),
+    });
+    result.ok().map(|body| ValidDwpResponse { dwp, body })
+}
+
+/// Probes every domain for each of the `seconds` seconds starting at `video_data`'s time.
+/// Paths built from the unix time are tried first, then paths built from the second.
+pub async fn get_valid_dwp(
+    domains: &[Arc<String>],
+    seconds: i64,
+    video_data: VideoData,
+    client: HttpClient,
+    milliseconds_retry: u64,
+    head_start: HeadStart,
+) -> anyhow::Result<ValidDwpResponse<Arc<String>>> {
+    let domain_with_paths_list = video_data.get_domain_with_paths_list(domains, seconds, true);
+    let dwp_and_body = get_first_valid_dwp(
+        domain_with_paths_list,
+        client.clone(),
+        milliseconds_retry,
+        head_start,
+    )
+    .await;
+    if let Some(Ok(dwp_and_body)) = dwp_and_body {
+        return Ok(dwp_and_body);
+    }
+    let domain_with_paths_list = video_data.get_domain_with_paths_list(domains, seconds, false);
+    let dwp_and_body = get_first_valid_dwp(
+        domain_with_paths_list,
+        client,
+        milliseconds_retry,
+        head_start,
+    )
+    .await;
+    match dwp_and_body {
+        Some(dwp_and_body) => dwp_and_body,
+        None => Err(anyhow!("no domains supplied")),
+    }
+}
+
+/// Decodes the playlist that was found, mutes its segments and makes their urls explicit.
+pub fn prepare_media_playlist<T: Clone + 'static + Send + Display>(
+    response: &ValidDwpResponse<T>,
+) -> anyhow::Result<MediaPlaylist> {
+    let mut playlist = crate::decode_media_playlist_filter_nil_segments(response.body.clone())?;
+    crate::mute_media_segments(&mut playlist);
+    response.dwp.make_paths_explicit(&mut playlist);
+    Ok(playlist)
+}
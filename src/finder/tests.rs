@@ -0,0 +1,160 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::url_path_to_video_data;
+
+/// Serves a three segment playlist for `video_data` under `domain`, where the second segment is
+/// missing, and returns a client for it.
+#[cfg(test)]
+fn serve_vod(domain: &str, video_data: VideoData) -> HttpClient {
+    let dwp = DomainWithPath {
+        domain: Arc::new(domain.to_string()),
+        path: Arc::new(video_data.get_video_path(true)),
+    };
+    let cdn = crate::FakeCdn::new();
+    cdn.serve(
+        dwp.get_index_dvr_url(),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\n0.ts\n#EXTINF:10,\n1.ts\n#EXTINF:10,\n2.ts\n#EXT-X-ENDLIST\n",
+    );
+    for uri in ["0.ts", "2.ts"] {
+        cdn.serve(
+            format!("{}{}/chunked/{}", dwp.domain, dwp.path.url_path, uri),
+            "",
+        );
+    }
+    HttpClient::with_transport(cdn, &RateLimitConfig::default())
+}
+
+#[test]
+fn test_vod_source_search_window() {
+    let data = StreamsChartsData {
+        streamer_name: "streamer".to_string(),
+        video_id: "123".to_string(),
+        utc_time: "24-09-2022 17:02".to_string(),
+    };
+    assert_eq!(VodSource::StreamsCharts(data).search_window(), 60);
+    assert_eq!(VodSource::Url("https://x/".to_string()).search_window(), 1);
+}
+
+#[tokio::test]
+async fn test_vod_finder_finds_and_filters() {
+    let video_data = url_path_to_video_data("abc_streamer_123_1664038929").unwrap();
+    let domain = "https://a.invalid/";
+    let client = serve_vod(domain, video_data.with_offset(0));
+    let found = VodFinder::new(video_data.with_offset(0))
+        .domains([domain])
+        .client(client)
+        .milliseconds_retry(0)
+        .filter(SegmentFilter::All)
+        .find()
+        .await
+        .unwrap();
+    assert_eq!(found.dwp.domain.as_str(), domain);
+    assert_eq!(found.playlist.segments.len(), 2);
+    assert!(found.playlist.segments[1].uri.ends_with("/chunked/2.ts"));
+    assert_eq!(found.stats.num_segments, 3);
+    assert_eq!(found.stats.num_valid_segments, 2);
+    assert_eq!(found.stats.num_checked, 3);
+    assert_eq!(found.diagnostics.time_offset_seconds, 0);
+    assert!(found.diagnostics.unix_time_path);
+    assert!(found.diagnostics.validation_error.is_none());
+    assert_eq!(found.segment_cache.segments.len(), 3);
+}
+
+#[tokio::test]
+async fn test_vod_finder_uses_the_resolution_cache() {
+    let video_data = url_path_to_video_data("abc_streamer_123_1664038929").unwrap();
+    let dwp = DomainWithPath {
+        domain: Arc::new("https://b.invalid/".to_string()),
+        path: Arc::new(video_data.with_offset(0).get_video_path(true)),
+    };
+    let cdn = crate::FakeCdn::new();
+    cdn.serve(
+        dwp.get_index_dvr_url(),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\n0.ts\n#EXT-X-ENDLIST\n",
+    );
+    let client = HttpClient::with_transport(cdn.clone(), &RateLimitConfig::default());
+    let events = Arc::new(Mutex::new(vec![]));
+    client.set_event_sink(Arc::new({
+        let events = Arc::clone(&events);
+        move |event: &Event| events.lock().unwrap().push(event.clone())
+    }));
+    let find = |cache, ttl| {
+        VodFinder::new(video_data.with_offset(0))
+            .domains(["https://a.invalid/", "https://b.invalid/"])
+            .client(client.clone())
+            .milliseconds_retry(0)
+            .resolution_cache(cache, ttl)
+            .find()
+    };
+    let found = find(ResolutionCache::default(), Duration::from_secs(60))
+        .await
+        .unwrap();
+    let num_search_requests = cdn.requests().len();
+    assert!(num_search_requests > 1);
+
+    // a fresh entry is used without any request
+    let found = find(found.resolution_cache.unwrap(), Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(cdn.requests().len(), num_search_requests);
+    assert_eq!(found.dwp.get_index_dvr_url(), dwp.get_index_dvr_url());
+    assert_eq!(found.playlist.segments.len(), 1);
+
+    // a stale entry is fetched again from its url and compared with the cached playlist
+    cdn.serve(
+        dwp.get_index_dvr_url(),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\n0.ts\n#EXTINF:10,\n1.ts\n#EXT-X-ENDLIST\n",
+    );
+    let found = find(found.resolution_cache.unwrap(), Duration::ZERO)
+        .await
+        .unwrap();
+    assert_eq!(cdn.requests().len(), num_search_requests + 1);
+    assert_eq!(found.playlist.segments.len(), 2);
+    assert!(events
+        .lock()
+        .unwrap()
+        .contains(&Event::CachedPlaylistChanged {
+            url: dwp.get_index_dvr_url()
+        }));
+    let cache = found.resolution_cache.unwrap();
+    assert_eq!(cache.entries.len(), 1);
+    assert!(cache
+        .entries
+        .values()
+        .all(|cached| cached.body.contains("1.ts")));
+}
+
+#[tokio::test]
+async fn test_vod_finder_prefers_valid_unmuted_segments() {
+    let video_data = url_path_to_video_data("abc_streamer_123_1664038929").unwrap();
+    let dwp = DomainWithPath {
+        domain: Arc::new("https://a.invalid/".to_string()),
+        path: Arc::new(video_data.with_offset(0).get_video_path(true)),
+    };
+    let chunked = format!("https://a.invalid/{}/chunked/", dwp.path.url_path);
+    let cdn = crate::FakeCdn::new();
+    cdn.serve(
+        dwp.get_index_dvr_url(),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\n0.ts\n#EXTINF:10,\n1-unmuted.ts\n#EXTINF:10,\n2-unmuted.ts\n#EXT-X-ENDLIST\n",
+    );
+    for file in ["0.ts", "1-unmuted.ts", "1-muted.ts", "2-muted.ts"] {
+        cdn.serve(format!("{}{}", chunked, file), vec![0; 3]);
+    }
+    let client = HttpClient::with_transport(cdn, &RateLimitConfig::default());
+    let found = VodFinder::new(video_data)
+        .domains(["https://a.invalid/"])
+        .client(client)
+        .milliseconds_retry(0)
+        .transforms("mute,unmute,explicit".parse().unwrap())
+        .find()
+        .await
+        .unwrap();
+    let uris = found
+        .playlist
+        .segments
+        .iter()
+        .map(|segment| segment.uri.strip_prefix(&chunked).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(uris, ["0.ts", "1-unmuted.ts", "2-muted.ts"]);
+}
@@ -0,0 +1,355 @@
+mod tests;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use chrono::{SecondsFormat, Utc};
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Version,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionFailed, Transport, TransportResponse};
+
+/// An HTTP Archive (HAR 1.2) with the subset of fields that the recorder writes.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    /// Milliseconds from sending the request until the body was read.
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub timings: HarTimings,
+    /// Set instead of a response if the server couldn't be reached. The status is 0 then.
+    #[serde(rename = "_error", default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub headers: Vec<HarHeader>,
+    pub query_string: Vec<HarHeader>,
+    pub cookies: Vec<HarHeader>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub headers: Vec<HarHeader>,
+    pub cookies: Vec<HarHeader>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    /// The length of the body, or -1 if it is unknown because the body wasn't read.
+    pub size: i64,
+    pub mime_type: String,
+    /// Only recorded for playlists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HarTimings {
+    pub send: f64,
+    /// Milliseconds until the headers arrived.
+    pub wait: f64,
+    /// Milliseconds spent reading the body.
+    pub receive: f64,
+}
+
+/// Collects every request made through the transports it wraps.
+/// Clones share the same entries, so one recorder can wrap every client that is built.
+/// The bodies of the playlists are kept, so that a search can be replayed from the recording.
+#[derive(Clone, Default)]
+pub struct HarRecorder {
+    entries: Arc<Mutex<Vec<HarEntry>>>,
+}
+
+impl HarRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `request_headers` are the headers that `transport` sends with every request, e.g. the
+    /// default headers of a `reqwest::Client`, which it doesn't expose. Credentials among them
+    /// are redacted.
+    pub fn wrap(
+        &self,
+        transport: Arc<dyn Transport>,
+        request_headers: HeaderMap,
+    ) -> Arc<dyn Transport> {
+        Arc::new(RecordingTransport {
+            inner: transport,
+            recorder: self.clone(),
+            request_headers: to_har_headers(&request_headers),
+        })
+    }
+
+    pub fn to_har(&self) -> Har {
+        Har {
+            log: HarLog {
+                version: "1.2".to_string(),
+                creator: HarCreator {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries: self.entries.lock().unwrap().clone(),
+            },
+        }
+    }
+
+    fn push(&self, entry: HarEntry) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        entries.len() - 1
+    }
+}
+
+struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    recorder: HarRecorder,
+    request_headers: Vec<HarHeader>,
+}
+
+impl Transport for RecordingTransport {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        Box::pin(async move {
+            let started_date_time = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+            let start = Instant::now();
+            let result = self.inner.get(url).await;
+            let wait = start.elapsed().as_secs_f64() * 1000.0;
+            let mut entry = HarEntry {
+                started_date_time,
+                time: wait,
+                request: HarRequest {
+                    method: "GET".to_string(),
+                    url: url.to_string(),
+                    headers: self.request_headers.clone(),
+                    headers_size: -1,
+                    body_size: 0,
+                    ..HarRequest::default()
+                },
+                timings: HarTimings {
+                    wait,
+                    ..HarTimings::default()
+                },
+                ..HarEntry::default()
+            };
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => {
+                    entry.response.content.size = -1;
+                    entry.error = Some(format!("{:#}", err));
+                    self.recorder.push(entry);
+                    return Err(err);
+                }
+            };
+            let version = response.version();
+            entry.request.http_version = format!("{:?}", version);
+            entry.response = HarResponse {
+                status: response.status(),
+                http_version: format!("{:?}", version),
+                headers: to_har_headers(response.headers()),
+                content: HarContent {
+                    size: -1,
+                    mime_type: header_value(response.headers(), "content-type"),
+                    text: None,
+                },
+                headers_size: -1,
+                body_size: response.content_length().map_or(-1, |len| len as i64),
+                ..HarResponse::default()
+            };
+            let index = self.recorder.push(entry);
+            let keep_body = is_playlist_url(url);
+            let recorder = self.recorder.clone();
+            let url = response.url().to_string();
+            let status = response.status();
+            let headers = response.headers().clone();
+            let content_length = response.content_length();
+            let body = Box::pin(async move {
+                let start = Instant::now();
+                let bytes = response.bytes().await?;
+                let receive = start.elapsed().as_secs_f64() * 1000.0;
+                let mut entries = recorder.entries.lock().unwrap();
+                let entry = &mut entries[index];
+                entry.timings.receive = receive;
+                entry.time += receive;
+                entry.response.content.size = bytes.len() as i64;
+                entry.response.body_size = bytes.len() as i64;
+                if keep_body {
+                    entry.response.content.text =
+                        std::str::from_utf8(&bytes).ok().map(String::from);
+                }
+                Ok(bytes)
+            });
+            Ok(
+                TransportResponse::streaming(&url, status, headers, content_length, body)
+                    .with_version(version),
+            )
+        })
+    }
+}
+
+/// Answers every request from a recording without touching the network.
+/// A url that was requested several times gets the recorded responses in order,
+/// and the last one after that. Urls that weren't recorded fail like an unreachable server.
+pub struct ReplayTransport {
+    entries: HashMap<String, Vec<HarEntry>>,
+    served: Mutex<HashMap<String, usize>>,
+}
+
+impl ReplayTransport {
+    pub fn new(har: Har) -> Self {
+        let mut entries: HashMap<String, Vec<HarEntry>> = HashMap::new();
+        for entry in har.log.entries {
+            entries
+                .entry(entry.request.url.clone())
+                .or_default()
+                .push(entry);
+        }
+        Self {
+            entries,
+            served: Mutex::default(),
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        let entry = self.entries.get(url).map(|entries| {
+            let mut served = self.served.lock().unwrap();
+            let count = served.entry(url.to_string()).or_default();
+            let entry = &entries[(*count).min(entries.len() - 1)];
+            *count += 1;
+            entry.clone()
+        });
+        Box::pin(async move {
+            let Some(entry) = entry else {
+                return Err(ConnectionFailed(format!("{} was not recorded", url)).into());
+            };
+            if let Some(error) = entry.error {
+                return Err(ConnectionFailed(error).into());
+            }
+            let response = entry.response;
+            let mut headers = HeaderMap::new();
+            for header in &response.headers {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(header.name.as_bytes()),
+                    HeaderValue::from_str(&header.value),
+                ) {
+                    headers.append(name, value);
+                }
+            }
+            let content_length = u64::try_from(response.body_size).ok();
+            if response.content.text.is_none() && is_playlist_url(url) && response.body_size > 0 {
+                return Err(anyhow!(
+                    "the body of {} is missing from the recording, record it again",
+                    url
+                ));
+            }
+            let version = parse_http_version(&response.http_version);
+            let body = Bytes::from(response.content.text.unwrap_or_default());
+            Ok(TransportResponse::streaming(
+                url,
+                response.status,
+                headers,
+                content_length,
+                Box::pin(async move { Ok(body) }),
+            )
+            .with_version(version))
+        })
+    }
+}
+
+fn is_playlist_url(url: &str) -> bool {
+    url.split('?').next().unwrap_or(url).ends_with(".m3u8")
+}
+
+/// Headers that carry credentials, whose values are left out of recordings.
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+fn to_har_headers(headers: &HeaderMap) -> Vec<HarHeader> {
+    headers
+        .iter()
+        .map(|(name, value)| HarHeader {
+            name: name.to_string(),
+            value: if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).to_string()
+            },
+        })
+        .collect()
+}
+
+/// The inverse of formatting a `Version` with `{:?}`, which is how the recorder writes it.
+fn parse_http_version(version: &str) -> Version {
+    match version {
+        "HTTP/0.9" => Version::HTTP_09,
+        "HTTP/1.0" => Version::HTTP_10,
+        "HTTP/2.0" | "HTTP/2" | "h2" => Version::HTTP_2,
+        "HTTP/3.0" | "HTTP/3" | "h3" => Version::HTTP_3,
+        _ => Version::HTTP_11,
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
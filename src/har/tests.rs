@@ -0,0 +1,144 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::{FakeCdn, FakeResponse, HttpClient, RateLimitConfig};
+
+#[tokio::test]
+async fn test_replay_recorded_traffic() {
+    let cdn = FakeCdn::new();
+    cdn.serve("https://a.invalid/x/index-dvr.m3u8", "#EXTM3U\n");
+    cdn.serve("https://a.invalid/x/0.ts", vec![0; 10]);
+    cdn.insert("https://b.invalid/", FakeResponse::ConnectionFailed);
+    let recorder = HarRecorder::new();
+    let client = HttpClient::with_transport(
+        recorder.wrap(cdn, HeaderMap::new()),
+        &RateLimitConfig::default(),
+    );
+    let response = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap();
+    assert_eq!(client.bytes(response).await.unwrap(), "#EXTM3U\n");
+    // segments are only checked, their body isn't read
+    let response = client.get("https://a.invalid/x/0.ts").await.unwrap();
+    assert_eq!(response.content_length(), Some(10));
+    assert!(client.get("https://b.invalid/").await.is_err());
+
+    // the HAR file survives a round trip through JSON
+    let har = recorder.to_har();
+    let har: Har = serde_json::from_str(&serde_json::to_string(&har).unwrap()).unwrap();
+    assert_eq!(har.log.entries.len(), 3);
+    assert_eq!(
+        har.log.entries[0].response.content.text.as_deref(),
+        Some("#EXTM3U\n")
+    );
+    assert_eq!(har.log.entries[1].response.content.text, None);
+    assert_eq!(har.log.entries[2].response.status, 0);
+
+    let client = HttpClient::with_transport(
+        Arc::new(ReplayTransport::new(har)),
+        &RateLimitConfig::default(),
+    );
+    let response = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(client.bytes(response).await.unwrap(), "#EXTM3U\n");
+    let response = client.get("https://a.invalid/x/0.ts").await.unwrap();
+    assert_eq!(response.content_length(), Some(10));
+    let err = client.get("https://b.invalid/").await.unwrap_err();
+    assert!(crate::is_connection_error(&err));
+    assert!(client.get("https://c.invalid/").await.is_err());
+}
+
+#[tokio::test]
+async fn test_replay_without_playlist_body() {
+    let recorder = HarRecorder::new();
+    let cdn = FakeCdn::new();
+    cdn.serve("https://a.invalid/x/index-dvr.m3u8", "#EXTM3U\n");
+    let client = HttpClient::with_transport(
+        recorder.wrap(cdn, HeaderMap::new()),
+        &RateLimitConfig::default(),
+    );
+    let response = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap();
+    client.bytes(response).await.unwrap();
+    let mut har = recorder.to_har();
+    // recordings made before bodies were always kept
+    har.log.entries[0].response.content.text = None;
+
+    let client = HttpClient::with_transport(
+        Arc::new(ReplayTransport::new(har)),
+        &RateLimitConfig::default(),
+    );
+    let err = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("missing from the recording"));
+}
+
+#[tokio::test]
+async fn test_record_request_headers_and_version() {
+    let recorder = HarRecorder::new();
+    let cdn = FakeCdn::new();
+    cdn.serve("https://a.invalid/x/index-dvr.m3u8", "#EXTM3U\n");
+    let transport: Arc<dyn Transport> = Arc::new(Http2(cdn));
+    let mut headers = HeaderMap::new();
+    headers.insert("user-agent", HeaderValue::from_static("vods-test"));
+    headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+    headers.insert("cookie", HeaderValue::from_static("session=secret"));
+    let client = HttpClient::with_transport(
+        recorder.wrap(transport, headers),
+        &RateLimitConfig::default(),
+    );
+    let response = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap();
+    assert_eq!(response.version(), Version::HTTP_2);
+    client.bytes(response).await.unwrap();
+
+    let har = recorder.to_har();
+    let entry = &har.log.entries[0];
+    assert_eq!(entry.request.http_version, "HTTP/2.0");
+    assert_eq!(entry.response.http_version, "HTTP/2.0");
+    let header = |name: &str| {
+        entry
+            .request
+            .headers
+            .iter()
+            .find(|header| header.name == name)
+            .map(|header| header.value.as_str())
+    };
+    assert_eq!(header("user-agent"), Some("vods-test"));
+    assert_eq!(header("authorization"), Some("[redacted]"));
+    assert_eq!(header("cookie"), Some("[redacted]"));
+
+    let client = HttpClient::with_transport(
+        Arc::new(ReplayTransport::new(har)),
+        &RateLimitConfig::default(),
+    );
+    let response = client
+        .get("https://a.invalid/x/index-dvr.m3u8")
+        .await
+        .unwrap();
+    assert_eq!(response.version(), Version::HTTP_2);
+}
+
+/// Answers like the wrapped transport, but over HTTP/2.
+#[cfg(test)]
+struct Http2(Arc<FakeCdn>);
+
+#[cfg(test)]
+impl Transport for Http2 {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        Box::pin(async move {
+            let response = self.0.get(url).await?;
+            Ok(response.with_version(Version::HTTP_2))
+        })
+    }
+}
@@ -1,2 +1,44 @@
+mod bisect;
+mod cache;
+mod cancel;
+mod check;
+mod client;
+mod concurrency;
+mod events;
+mod fakecdn;
+mod finder;
+mod har;
+mod limiter;
+mod monitor;
 mod parse;
+mod preflight;
+mod proxy;
+mod ranking;
+mod sampling;
+mod seek;
+#[cfg(test)]
+mod testing;
+mod transform;
+mod transport;
+mod watchdog;
+pub use self::bisect::*;
+pub use self::cache::*;
+pub use self::cancel::*;
+pub use self::check::*;
+pub use self::client::*;
+pub use self::concurrency::*;
+pub use self::events::*;
+pub use self::fakecdn::*;
+pub use self::finder::*;
+pub use self::har::*;
+pub use self::limiter::*;
+pub use self::monitor::*;
 pub use self::parse::*;
+pub use self::preflight::*;
+pub use self::proxy::*;
+pub use self::ranking::*;
+pub use self::sampling::*;
+pub use self::seek::*;
+pub use self::transform::*;
+pub use self::transport::*;
+pub use self::watchdog::*;
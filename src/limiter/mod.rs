@@ -0,0 +1,161 @@
+mod tests;
+
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Limits shared by every request made through one `HttpClient`.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    pub requests_per_second: Option<f64>,
+    pub bytes_per_second: Option<f64>,
+    /// The total number of requests allowed before everything is aborted.
+    pub max_requests: Option<u64>,
+}
+
+/// A token bucket that refills at `rate` tokens per second up to one second's worth of tokens.
+/// Taking more tokens than are available puts the bucket into debt,
+/// and the caller sleeps until the debt is paid off.
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64) -> Self {
+        let rate = rate.max(f64::MIN_POSITIVE);
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `amount` tokens and returns how long the caller has to wait before proceeding.
+    fn take_delay(&self, amount: f64) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+        state.last_refill = now;
+        state.tokens -= amount;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+
+    pub async fn take(&self, amount: f64) {
+        let delay = self.take_delay(amount);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BudgetExhausted {
+    pub max_requests: u64,
+}
+
+impl Display for BudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request budget of {} exhausted", self.max_requests)
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+/// A hard cap on the total number of requests.
+pub struct RequestBudget {
+    max_requests: u64,
+    spent: AtomicU64,
+}
+
+impl RequestBudget {
+    pub fn new(max_requests: u64) -> Self {
+        Self {
+            max_requests,
+            spent: AtomicU64::new(0),
+        }
+    }
+
+    pub fn try_spend(&self) -> Result<(), BudgetExhausted> {
+        let spent = self.spent.fetch_add(1, Ordering::Relaxed);
+        if spent < self.max_requests {
+            Ok(())
+        } else {
+            Err(BudgetExhausted {
+                max_requests: self.max_requests,
+            })
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.spent.load(Ordering::Relaxed) >= self.max_requests
+    }
+
+    pub fn spent(&self) -> u64 {
+        self.spent.load(Ordering::Relaxed).min(self.max_requests)
+    }
+}
+
+/// The rate limiters and the budget described by a `RateLimitConfig`.
+pub struct Limits {
+    requests: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    budget: Option<RequestBudget>,
+}
+
+impl Limits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            requests: config.requests_per_second.map(TokenBucket::new),
+            bytes: config.bytes_per_second.map(TokenBucket::new),
+            budget: config.max_requests.map(RequestBudget::new),
+        }
+    }
+
+    /// Spends one request from the budget.
+    /// A request that is sent again after a client rebuild is still one request.
+    pub fn spend_request(&self) -> Result<(), BudgetExhausted> {
+        match &self.budget {
+            Some(budget) => budget.try_spend(),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for the request rate limit before every send, including resends.
+    pub async fn before_send(&self) {
+        if let Some(requests) = &self.requests {
+            requests.take(1.0).await;
+        }
+    }
+
+    /// Waits for the byte rate limit after `num_bytes` have been read.
+    pub async fn after_read(&self, num_bytes: usize) {
+        if let Some(bytes) = &self.bytes {
+            bytes.take(num_bytes as f64).await;
+        }
+    }
+
+    pub fn budget(&self) -> Option<&RequestBudget> {
+        self.budget.as_ref()
+    }
+}
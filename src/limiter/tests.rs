@@ -0,0 +1,30 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_request_budget() {
+    let budget = RequestBudget::new(2);
+    assert!(budget.try_spend().is_ok());
+    assert!(!budget.is_exhausted());
+    assert!(budget.try_spend().is_ok());
+    assert!(budget.is_exhausted());
+    assert!(budget.try_spend().is_err());
+    assert_eq!(budget.spent(), 2);
+}
+
+#[test]
+fn test_token_bucket_allows_burst_then_waits() {
+    let bucket = TokenBucket::new(10.0);
+    for _ in 0..10 {
+        assert_eq!(bucket.take_delay(1.0), Duration::ZERO);
+    }
+    let delay = bucket.take_delay(1.0);
+    assert!(delay > Duration::from_millis(50) && delay <= Duration::from_millis(100));
+}
+
+#[test]
+fn test_token_bucket_debt() {
+    let bucket = TokenBucket::new(1000.0);
+    let delay = bucket.take_delay(3000.0);
+    assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_millis(2000));
+}
@@ -1,18 +1,42 @@
-use std::{fmt::Display, fs, io::BufWriter, path::PathBuf, time::Duration};
+use std::{
+    fmt::Display,
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use anyhow::anyhow;
-use clap::{Args, Parser, Subcommand};
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDateTime, Utc};
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
 use m3u8_rs::MediaPlaylist;
-use reqwest::Client;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, USER_AGENT},
+    Client, Proxy,
+};
+use tokio::{net::TcpListener, select};
 use vods::{
-    self, DomainWithPath, StreamsChartsData, SullyGnomeData, TwitchTrackerData, ValidDwpResponse,
-    VideoData,
+    self, AlertThresholds, AvailabilityAlert, AvailabilityRecord, CancellationToken,
+    ClientBuildOptions, DomainStats, DomainWithPath, FakeCdnConfig, Har, HttpClient,
+    MonitorHistory, ReplayTransport, ResolutionCache, SegmentCache, SegmentCachePolicy,
+    SegmentChanges, SegmentFilter, StreamsChartsData, SullyGnomeData, TransformContext,
+    TransformPipeline, Transport, TwitchTrackerData, ValidSegmentsResponse, VideoData, VodFinder,
 };
 
+mod cli;
+
+use cli::{Config, DomainStore, Settings};
+
 #[derive(Parser)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// TOML config file [default: $XDG_CONFIG_HOME/vods/config.toml]
+    #[arg(long, global = true, env = "VODS_CONFIG")]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    settings: Settings,
 }
 
 #[derive(Clone, Subcommand)]
@@ -26,6 +50,87 @@ enum Commands {
     /// Using sullygnome.com data, get an .m3u8 file which can be viewed in a media player.
     #[command(name = "sg-manual-get-m3u8")]
     SullyGnome(SullyGnomeArgs),
+    /// Using any url of a VOD (e.g. a storyboard url), get an .m3u8 file which can be viewed in a media player.
+    /// The domain of the url is added to the domains file.
+    #[command(name = "url-get-m3u8")]
+    Url(UrlArgs),
+    /// Check the segments of a playlist written earlier again and write an updated playlist.
+    /// Segments that died or came back since are listed in a '.changes.txt' file next to it.
+    Check(CheckArgs),
+    /// Check the playlists in the output directory for segments that disappeared from the CDN.
+    /// Flags VODs whose availability dropped since the last check or that are close to the
+    /// typical retention ages of 7, 14 and 60 days.
+    Monitor(MonitorArgs),
+    /// Find the part of a VOD that was streamed at a UTC time, e.g. 'vods at {playlist} 21:14'.
+    /// Prints the segment, where to seek to in the playlist, and the twitch.tv url.
+    At(AtArgs),
+    /// Serve fake VODs under the CDN's url layout, with configurable faults, for testing
+    /// without internet access. Point the other commands at it with '--domain'.
+    #[command(name = "fake-cdn")]
+    FakeCdn(FakeCdnArgs),
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommands),
+    /// List or edit the CDN domains that are searched.
+    #[command(subcommand)]
+    Domains(DomainsCommands),
+    /// List or clear the urls that past searches resolved and the segment checks.
+    #[command(subcommand)]
+    Cache(CacheCommands),
+}
+
+#[derive(Clone, Subcommand)]
+enum CacheCommands {
+    /// Print every cached VOD with its url and when it was last fetched, and count the cached segments.
+    List,
+    /// Delete the cache files.
+    Clear,
+}
+
+#[derive(Clone, Subcommand)]
+enum DomainsCommands {
+    /// Print the domains that will be searched.
+    List,
+    /// Add domains to the domains file. Full VOD urls are accepted too.
+    Add {
+        #[arg(required = true, value_name = "DOMAIN")]
+        entries: Vec<String>,
+    },
+    /// Show how often each domain held a VOD, by VOD age, and how healthy it has been.
+    Stats,
+    /// Remove domains from the domains file.
+    Remove {
+        #[arg(required = true, value_name = "DOMAIN")]
+        entries: Vec<String>,
+    },
+}
+
+#[derive(Clone, Subcommand)]
+enum ConfigCommands {
+    /// Print the effective configuration after applying the config file, environment and flags.
+    Show,
+}
+
+#[derive(Args, Clone)]
+struct ValidationArgs {
+    /// Filter out all of the invalid segments in the m3u8 file
+    #[arg(long, conflicts_with_all = ["sample", "bisect"])]
+    filter_invalid: bool,
+    /// Filter out the invalid segments, assuming that they come in long runs, e.g. because the
+    /// start or the end of the VOD was deleted. This takes far fewer requests than
+    /// --filter-invalid.
+    #[arg(long, conflicts_with = "sample")]
+    bisect: bool,
+    /// With --bisect, check every this many segments before looking for boundaries in between
+    #[arg(long, default_value_t = 32)]
+    bisect_stride: usize,
+    /// Check a random sample of this many segments, spread over the whole VOD, and estimate how
+    /// many segments are available
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    sample: Option<usize>,
+    /// Filter out the invalid segments if the sample finds some valid and some invalid segments
+    #[arg(long, requires = "sample")]
+    escalate: bool,
 }
 
 #[derive(Args, Clone)]
@@ -39,9 +144,8 @@ struct TwitchTrackerArgs {
     /// stream UTC start time in the format '2006-01-02 15:04:05' (year-month-day hour:minute:second)
     #[arg(long)]
     time: String,
-    /// Filter out all of the invalid segments in the m3u8 file with concurrency level
-    #[arg(long)]
-    filter_invalid: bool,
+    #[command(flatten)]
+    validation: ValidationArgs,
 }
 
 #[derive(Args, Clone)]
@@ -55,9 +159,8 @@ struct StreamsChartsArgs {
     /// stream UTC start time in the format '02-01-2006 15:04' (day-month-year hour:minute)
     #[arg(long)]
     time: String,
-    /// Filter out all of the invalid segments in the m3u8 file with concurrency level
-    #[arg(long)]
-    filter_invalid: bool,
+    #[command(flatten)]
+    validation: ValidationArgs,
 }
 
 #[derive(Args, Clone)]
@@ -71,9 +174,66 @@ struct SullyGnomeArgs {
     /// stream UTC start time in the format '2006-01-02T15:04:05Z' (year-month-dayThour:minute:secondZ)
     #[arg(long)]
     time: String,
-    /// Filter out all of the invalid segments in the m3u8 file
+    #[command(flatten)]
+    validation: ValidationArgs,
+}
+
+#[derive(Args, Clone)]
+struct UrlArgs {
+    /// url of the VOD, e.g. 'https://d1m7jfoe9zdc1j.cloudfront.net/c5992ececce7bd7d350d_gmhikaru_47198535725_1664038929/storyboards/1600104857-info.json'
     #[arg(long)]
-    filter_invalid: bool,
+    url: String,
+    #[command(flatten)]
+    validation: ValidationArgs,
+}
+
+#[derive(Args, Clone)]
+struct CheckArgs {
+    /// .m3u8 file written by one of the get-m3u8 commands
+    playlist: PathBuf,
+    /// Where to write the updated playlist [default: overwrite the checked playlist]
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Clone)]
+struct MonitorArgs {
+    /// Directory with the playlists [default: the output directory]
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    /// Number of segments to check in each playlist, spread evenly. 0 checks every segment.
+    #[arg(long, default_value_t = 20)]
+    sample: usize,
+    /// Keep running and check again after this many minutes
+    #[arg(long)]
+    interval_minutes: Option<u64>,
+    /// Report a drop in availability larger than this fraction
+    #[arg(long, default_value_t = AlertThresholds::default().drop)]
+    drop_threshold: f64,
+    /// Warn this many days before a VOD reaches a typical retention age
+    #[arg(long, default_value_t = AlertThresholds::default().retention_warning_days)]
+    retention_warning_days: i64,
+}
+
+#[derive(Args, Clone)]
+struct AtArgs {
+    /// .m3u8 file written by one of the get-m3u8 commands, or any url of the VOD
+    vod: String,
+    /// UTC time, e.g. '2022-09-24 18:14:00', or just '18:14' for the day the stream started
+    time: String,
+}
+
+#[derive(Args, Clone)]
+struct FakeCdnArgs {
+    /// TOML file with the domains and VODs to serve [default: a built-in example]
+    #[arg(long)]
+    vods: Option<PathBuf>,
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    /// Print every request and how it was answered
+    #[arg(long)]
+    log_requests: bool,
 }
 
 fn duration_to_human_readable(dur: &Duration) -> String {
@@ -83,18 +243,16 @@ fn duration_to_human_readable(dur: &Duration) -> String {
     format!("{:0>2}h{:0>2}m{:0>2}s", hours, minutes, secs)
 }
 
+/// Returns the path that was written. A `partial` playlist, whose segments weren't all checked,
+/// is written next to where the complete one would go, see `cli::partial_path`.
 fn write_media_playlist<T: Clone + 'static + Send + Display>(
     mediapl: &MediaPlaylist,
     dwp: DomainWithPath<T>,
-) -> anyhow::Result<()> {
+    output_dir: &Path,
+    partial: bool,
+) -> anyhow::Result<PathBuf> {
     let video_data = dwp.get_video_data();
-    let mut path = PathBuf::from_iter(
-        vec![
-            "Downloads".to_string(),
-            video_data.streamer_name.to_string(),
-        ]
-        .iter(),
-    );
+    let mut path = output_dir.join(video_data.streamer_name.as_str());
     fs::create_dir_all(&path)?;
     let rounded_duration = vods::get_media_playlist_duration(mediapl);
     path.push(format!(
@@ -102,13 +260,26 @@ fn write_media_playlist<T: Clone + 'static + Send + Display>(
         video_data,
         duration_to_human_readable(&rounded_duration)
     ));
+    if partial {
+        path = cli::partial_path(&path);
+    }
     let mut file_path = BufWriter::new(fs::File::create(&path)?);
     mediapl.write_to(&mut file_path)?;
-    Ok(())
+    Ok(path)
 }
 
-fn make_robust_client(config: &Config) -> Result<Client, reqwest::Error> {
-    Client::builder()
+fn make_robust_client(config: &Config, options: &ClientBuildOptions) -> anyhow::Result<Client> {
+    let mut builder = Client::builder().default_headers(default_request_headers(config)?);
+    if options.http1_only || config.http1_only {
+        builder = builder.http1_only();
+    }
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    for certificate in &config.root_certificates {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+    Ok(builder
         .timeout(Duration::from_millis(config.client_timeout_milliseconds))
         .http2_keep_alive_timeout(Duration::from_millis(
             config.http2_keep_alive_timeout_milliseconds,
@@ -120,94 +291,783 @@ fn make_robust_client(config: &Config) -> Result<Client, reqwest::Error> {
         .http2_adaptive_window(true)
         .use_rustls_tls()
         .trust_dns(true)
-        .build()
+        .build()?)
 }
 
-async fn get_valid_dwp(
-    domains: &[&'static str],
-    seconds: i64,
-    video_data: VideoData,
-    client: Client,
-    milliseconds_retry: u64,
-) -> anyhow::Result<ValidDwpResponse<&'static str>> {
-    let domain_with_paths_list = video_data.get_domain_with_paths_list(domains, seconds, true);
-    let dwp_and_body =
-        vods::get_first_valid_dwp(domain_with_paths_list, client.clone(), milliseconds_retry).await;
-    if let Some(Ok(dwp_and_body)) = dwp_and_body {
-        return Ok(dwp_and_body);
+/// The headers that every request is sent with, which are recorded along with the traffic.
+fn default_request_headers(config: &Config) -> anyhow::Result<HeaderMap> {
+    let mut headers = config.headers.clone();
+    if let Some(user_agent) = &config.user_agent {
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(user_agent).context("invalid user agent")?,
+        );
     }
-    let domain_with_paths_list = video_data.get_domain_with_paths_list(domains, seconds, false);
-    let dwp_and_body =
-        vods::get_first_valid_dwp(domain_with_paths_list, client, milliseconds_retry).await;
-    match dwp_and_body {
-        Some(dwp_and_body) => dwp_and_body,
-        None => Err(anyhow!("no domains supplied")),
+    Ok(headers)
+}
+
+fn build_client(config: &Config, token: &CancellationToken) -> anyhow::Result<HttpClient> {
+    let client = match &config.replay_file {
+        Some(path) => {
+            let contents =
+                fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+            let har: Har = serde_json::from_slice(&contents)
+                .with_context(|| format!("invalid HAR file {}", path.display()))?;
+            HttpClient::with_transport(Arc::new(ReplayTransport::new(har)), &config.rate_limit)
+        }
+        None => HttpClient::from_factory(
+            Arc::new({
+                let config = config.clone();
+                move |options| {
+                    let client: Arc<dyn Transport> =
+                        Arc::new(make_robust_client(&config, options)?);
+                    Ok(match &config.har_recorder {
+                        Some(recorder) => recorder.wrap(client, default_request_headers(&config)?),
+                        None => client,
+                    })
+                }
+            }),
+            &config.rate_limit,
+            config.watchdog.clone(),
+            config.proxy.clone(),
+        )?,
+    };
+    client.set_cancellation_token(token.clone());
+    Ok(client)
+}
+
+/// Checks the segments of `mediapl`, going through the segment cache if there is one.
+async fn validate_segments(
+    mediapl: MediaPlaylist,
+    client: HttpClient,
+    config: &Config,
+    policy: &SegmentCachePolicy,
+) -> anyhow::Result<ValidSegmentsResponse> {
+    let mut segment_cache: SegmentCache = match &config.segment_cache_file {
+        Some(path) => cli::load_json(path)?,
+        None => SegmentCache::default(),
+    };
+    let response = vods::get_media_playlist_with_valid_segments(
+        mediapl,
+        &config.concurrency,
+        client,
+        config.milliseconds_retry,
+        &mut segment_cache,
+        policy,
+    )
+    .await;
+    if let Some(path) = &config.segment_cache_file {
+        segment_cache.prune(&config.segment_cache, Utc::now().timestamp());
+        cli::save_json(path, &segment_cache)?;
     }
+    Ok(response)
 }
 
+/// `exact` is a path that is tried before the cache and the search, e.g. the one of a VOD url.
 async fn main_helper(
     seconds: i64,
     video_data: VideoData,
-    filter_invalid: bool,
+    exact: Option<DomainWithPath<Arc<String>>>,
+    validation: &ValidationArgs,
     config: &Config,
+    token: &CancellationToken,
 ) -> anyhow::Result<()> {
-    let video_data = video_data.with_offset(-1); // some m3u8 file names use a time that is 1 second minus the provided time
-    let client = make_robust_client(config)?;
-    let dwp_and_body = get_valid_dwp(
-        &vods::DOMAINS,
-        seconds + 1,
-        video_data,
-        client.clone(),
-        config.milliseconds_retry,
-    )
-    .await?;
-    println!("Found valid url {}", dwp_and_body.dwp.get_index_dvr_url());
-    let mut mediapl = vods::decode_media_playlist_filter_nil_segments(dwp_and_body.body)?;
-    vods::mute_media_segments(&mut mediapl);
-    dwp_and_body.dwp.make_paths_explicit(&mut mediapl);
-    if filter_invalid {
-        let num_total_segments = mediapl.segments.len();
-        mediapl = vods::get_media_playlist_with_valid_segments(
-            mediapl,
-            config.concurrent_filter_invalid,
-            client,
+    let drops_segments = validation.filter_invalid || validation.bisect || validation.escalate;
+    if drops_segments && !config.transforms.contains("filter") {
+        return Err(anyhow!(
+            "--filter-invalid, --bisect and --escalate drop the invalid segments with the 'filter' transform, which is missing from the transforms '{}'",
+            config.transforms
+        ));
+    }
+    let client = build_client(config, token)?;
+    let progress = cli::ProgressBar::attach(&client);
+    let filter = match validation.sample {
+        Some(size) => SegmentFilter::Sample {
+            size,
+            escalate: validation.escalate,
+        },
+        None if validation.bisect => SegmentFilter::Bisect {
+            stride: validation.bisect_stride,
+        },
+        None if validation.filter_invalid => SegmentFilter::All,
+        None => SegmentFilter::None,
+    };
+    let segment_cache: SegmentCache = match &config.segment_cache_file {
+        Some(path) => cli::load_json(path)?,
+        None => SegmentCache::default(),
+    };
+    let mut finder = VodFinder::new(video_data)
+        .window_seconds(seconds)
+        .domains(config.domains.iter().map(|domain| domain.to_string()))
+        .client(client.clone())
+        .milliseconds_retry(config.milliseconds_retry)
+        .head_start(config.head_start)
+        .preflight(config.preflight)
+        .filter(filter)
+        .concurrency(config.concurrency.clone())
+        .segment_cache(segment_cache, config.segment_cache)
+        .transforms(config.transforms.clone());
+    if let Some(dwp) = exact {
+        finder = finder.exact_path(dwp);
+    }
+    if let Some(path) = &config.resolution_cache_file {
+        finder = finder.resolution_cache(cli::load_json(path)?, config.resolution_cache_ttl);
+    }
+    let domain_stats = match &config.domain_stats_file {
+        Some(path) => Some(Arc::new(Mutex::new(cli::load_json::<DomainStats>(path)?))),
+        None => None,
+    };
+    if let Some(stats) = &domain_stats {
+        finder = finder.domain_stats(Arc::clone(stats));
+    }
+    let found = finder.find().await;
+    progress.finish();
+    if let (Some(stats), Some(path)) = (&domain_stats, &config.domain_stats_file) {
+        cli::save_json(path, &*stats.lock().unwrap())?;
+    }
+    let mut found = found?;
+    if let (Some(cache), Some(path)) = (&found.resolution_cache, &config.resolution_cache_file) {
+        cli::save_json(path, cache)?;
+    }
+    if let Some(path) = &config.segment_cache_file {
+        found
+            .segment_cache
+            .prune(&config.segment_cache, Utc::now().timestamp());
+        cli::save_json(path, &found.segment_cache)?;
+    }
+    let stats = &found.stats;
+    let partial = found.diagnostics.validation_error.is_some();
+    if let Some(err) = &found.diagnostics.validation_error {
+        eprintln!("Warning: {:#}", err);
+    }
+    for (range, offsets) in stats.valid_ranges.iter().zip(&stats.valid_offsets) {
+        println!(
+            "Valid segments {} to {} ({} to {})",
+            range.start,
+            range.end - 1,
+            duration_to_human_readable(&offsets.start),
+            duration_to_human_readable(&offsets.end)
+        );
+    }
+    if stats.filtered {
+        match filter {
+            SegmentFilter::Bisect { .. } => println!(
+                "{} valid segments out of {}, found with {} requests",
+                stats.num_valid_segments, stats.num_segments, stats.num_checked
+            ),
+            _ => println!(
+                "{} valid segments out of {}",
+                stats.num_valid_segments, stats.num_segments
+            ),
+        }
+        if stats.num_valid_segments == 0 {
+            return Err(anyhow!("0 valid segments found"));
+        }
+    }
+    let path = write_media_playlist(&found.playlist, found.dwp, &config.output_dir, partial)?;
+    if partial {
+        eprintln!(
+            "Warning: wrote the segments found to be valid so far to {}, run the search again for the rest",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Validates the segments of a playlist written earlier again and reports what changed.
+/// The VOD's playlist is fetched again too, so that segments that were filtered out before can
+/// come back.
+async fn check_playlist(
+    args: CheckArgs,
+    config: &Config,
+    token: &CancellationToken,
+) -> anyhow::Result<()> {
+    let contents = fs::read(&args.playlist)
+        .with_context(|| format!("failed to read {}", args.playlist.display()))?;
+    let old_playlist = vods::decode_media_playlist_filter_nil_segments(contents.into())?;
+    let first_segment = old_playlist
+        .segments
+        .first()
+        .context("playlist has no segments")?;
+    let dwp = vods::url_to_domain_with_path(&first_segment.uri)
+        .context("playlist urls are not explicit VOD urls")?;
+    learn_domain(&dwp.domain, config)?;
+    let client = build_client(config, token)?;
+    let progress = cli::ProgressBar::attach(&client);
+    // set if the VOD's playlist could be fetched, so that the segments can be stamped again
+    let mut context = None;
+    let mediapl = match dwp
+        .get_m3u8_body(client.clone(), config.milliseconds_retry)
+        .await
+    {
+        Ok(body) => {
+            let mut mediapl = vods::decode_media_playlist_filter_nil_segments(body)?;
+            context = Some(TransformContext::new(&mediapl, &dwp));
+            vods::mute_media_segments(&mut mediapl);
+            dwp.make_paths_explicit(&mut mediapl);
+            mediapl
+        }
+        Err(err) => {
+            println!(
+                "Failed to fetch {}, only checking the segments in the file: {:#}",
+                dwp.get_index_dvr_url(),
+                err
+            );
+            old_playlist.clone()
+        }
+    };
+    let num_total_segments = mediapl.segments.len();
+    // cached checks are skipped, since the point is to look again
+    let policy = SegmentCachePolicy {
+        ttl: Duration::ZERO,
+        ..config.segment_cache
+    };
+    let response = validate_segments(mediapl, client, config, &policy).await;
+    progress.finish();
+    let response = response?;
+    let stopped = response.error.map(|err| {
+        err.context(format!(
+            "stopped after checking {} segments out of {}",
+            response.num_checked, num_total_segments
+        ))
+    });
+    let mut playlist = response.playlist;
+    let changes = SegmentChanges::between(&old_playlist, &playlist);
+    match &stopped {
+        None => println!(
+            "{} valid segments out of {}, {} newly dead, {} recovered",
+            playlist.segments.len(),
+            num_total_segments,
+            changes.newly_dead.len(),
+            changes.recovered.len()
+        ),
+        Some(err) => eprintln!("Warning: {:#}", err),
+    }
+    // the fetched playlist has no date-times, so they are computed again for the file
+    let stamp = config.transforms.contains("program-date-time")
+        || old_playlist
+            .segments
+            .iter()
+            .any(|segment| segment.program_date_time.is_some());
+    if let Some(context) = context.filter(|_| stamp) {
+        TransformPipeline::new()
+            .with_program_date_time()
+            .apply(&mut playlist, &context)?;
+    }
+    let output = args.output.unwrap_or(args.playlist);
+    if stopped.is_some() {
+        // the segments that weren't checked would all look newly dead, so there is no report
+        let output = cli::partial_path(&output);
+        let mut file = BufWriter::new(fs::File::create(&output)?);
+        playlist.write_to(&mut file)?;
+        eprintln!(
+            "Warning: wrote the {} segments found to be valid so far to {}",
+            playlist.segments.len(),
+            output.display()
+        );
+        return Ok(());
+    }
+    let mut file = BufWriter::new(fs::File::create(&output)?);
+    playlist.write_to(&mut file)?;
+    println!("Wrote {}", output.display());
+    if !changes.is_empty() {
+        let report_path = output.with_extension("changes.txt");
+        fs::write(&report_path, changes.to_report())?;
+        println!("Wrote the changes to {}", report_path.display());
+    }
+    Ok(())
+}
+
+/// Checks a sample of the segments of every playlist under the archive directory once.
+async fn monitor_round(
+    args: &MonitorArgs,
+    dir: &Path,
+    history: &mut MonitorHistory,
+    client: &HttpClient,
+    progress: &cli::ProgressBar,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let thresholds = AlertThresholds {
+        drop: args.drop_threshold,
+        retention_warning_days: args.retention_warning_days,
+    };
+    // cached checks are skipped, since the point is to look again
+    let policy = SegmentCachePolicy {
+        ttl: Duration::ZERO,
+        ..config.segment_cache
+    };
+    let mut segment_cache: SegmentCache = match &config.segment_cache_file {
+        Some(path) => cli::load_json(path)?,
+        None => SegmentCache::default(),
+    };
+    let mut result = Ok(());
+    for path in cli::find_playlists(dir)? {
+        let name = path.display().to_string();
+        let key = cli::history_key(&path);
+        let playlist = match fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| vods::decode_media_playlist_filter_nil_segments(contents.into()))
+        {
+            Ok(playlist) => playlist,
+            Err(err) => {
+                println!("Skipping {}: {:#}", name, err);
+                continue;
+            }
+        };
+        let Some(first_segment) = playlist.segments.first() else {
+            println!("Skipping {}: no segments", name);
+            continue;
+        };
+        let Ok(dwp) = vods::url_to_domain_with_path(&first_segment.uri) else {
+            println!("Skipping {}: no explicit VOD urls", name);
+            continue;
+        };
+        learn_domain(&dwp.domain, config)?;
+        let mut sample = playlist.clone();
+        sample.segments = vods::evenly_spaced_indices(playlist.segments.len(), args.sample)
+            .into_iter()
+            .map(|i| playlist.segments[i].clone())
+            .collect();
+        let response = vods::get_media_playlist_with_valid_segments(
+            sample,
+            &config.concurrency,
+            client.clone(),
             config.milliseconds_retry,
+            &mut segment_cache,
+            &policy,
         )
         .await;
-        let num_valid_segments = mediapl.segments.len();
+        progress.finish();
+        if let Some(err) = response.error {
+            result = Err(err.context(format!("stopped while checking {}", name)));
+            break;
+        }
+        let now = Utc::now();
+        let record = AvailabilityRecord {
+            checked_at: now.timestamp(),
+            checked: response.num_checked,
+            valid: response.playlist.segments.len(),
+        };
+        history.record(&key, record);
+        let vod_age = now.naive_utc() - dwp.path.video_data.unix_time_seconds;
+        let alerts = history
+            .alerts(&key, vod_age, &thresholds)
+            .into_iter()
+            .map(|alert| match alert {
+                AvailabilityAlert::Dropped { from, to } => {
+                    format!("DROPPED from {:.0}% to {:.0}%", from * 100.0, to * 100.0)
+                }
+                AvailabilityAlert::NearRetention {
+                    age_days,
+                    limit_days,
+                } => format!(
+                    "{} days old, retention is often {} days",
+                    age_days, limit_days
+                ),
+                AvailabilityAlert::PastRetention {
+                    age_days,
+                    limit_days,
+                } => format!(
+                    "{} days old, past the longest retention of {} days",
+                    age_days, limit_days
+                ),
+            })
+            .collect::<Vec<_>>();
         println!(
-            "{} valid segments out of {}",
-            num_valid_segments, num_total_segments
+            "{}: {} of {} checked segments available ({:.0}%){}",
+            name,
+            record.valid,
+            record.checked,
+            record.availability() * 100.0,
+            if alerts.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", alerts.join(", "))
+            }
         );
-        if num_valid_segments == 0 {
-            return Err(anyhow!("0 valid segments found"));
+    }
+    if let Some(path) = &config.segment_cache_file {
+        segment_cache.prune(&config.segment_cache, Utc::now().timestamp());
+        cli::save_json(path, &segment_cache)?;
+    }
+    result
+}
+
+async fn monitor_archive(
+    args: MonitorArgs,
+    config: &Config,
+    token: &CancellationToken,
+) -> anyhow::Result<()> {
+    let dir = args.dir.clone().unwrap_or(config.output_dir.clone());
+    let client = build_client(config, token)?;
+    let progress = cli::ProgressBar::attach(&client);
+    let mut history: MonitorHistory = match &config.monitor_history_file {
+        Some(path) => cli::load_json(path)?,
+        None => MonitorHistory::default(),
+    };
+    loop {
+        let result = monitor_round(&args, &dir, &mut history, &client, &progress, config).await;
+        if let Some(path) = &config.monitor_history_file {
+            cli::save_json(path, &history)?;
+        }
+        result?;
+        let Some(minutes) = args.interval_minutes else {
+            return Ok(());
+        };
+        println!("Checking again in {} minutes", minutes);
+        select! {
+            _ = tokio::time::sleep(Duration::from_secs(minutes * 60)) => {}
+            _ = token.cancelled() => return Ok(()),
+        }
+    }
+}
+
+fn show_config(settings: &Settings, config_path: Option<&Path>) -> anyhow::Result<()> {
+    match config_path {
+        Some(path) => println!("# config file: {}", path.display()),
+        None => match cli::default_config_path() {
+            Some(path) => println!("# no config file found at {}", path.display()),
+            None => println!("# no config file found"),
+        },
+    }
+    print!("{}", toml::to_string(settings)?);
+    Ok(())
+}
+
+/// Parses a VOD url given by the user and remembers its domain.
+fn resolve_vod_url(url: &str, config: &Config) -> anyhow::Result<DomainWithPath<Arc<String>>> {
+    let dwp = vods::url_to_domain_with_path(url)?;
+    learn_domain(&dwp.domain, config)?;
+    Ok(dwp)
+}
+
+/// Adds `domain` to the domains file unless there is none, e.g. because of `--replay`.
+fn learn_domain(domain: &str, config: &Config) -> anyhow::Result<()> {
+    match &config.domains_file {
+        Some(path) => DomainStore::open(path)?.learn(domain),
+        None => Ok(()),
+    }
+}
+
+fn edit_domains(command: &DomainsCommands, settings: &Settings) -> anyhow::Result<()> {
+    let path = settings
+        .domains_file
+        .as_deref()
+        .context("no domains file configured")?;
+    let mut store = DomainStore::open(path)?;
+    if let Some(base_url) = &settings.base_url {
+        println!("# every request goes to the base url {}", base_url);
+    } else if store.domains != settings.domains {
+        println!("# the configured domain list overrides {}", path.display());
+    }
+    match command {
+        DomainsCommands::List => {
+            println!("# domains file: {}", path.display());
+            match &settings.base_url {
+                Some(base_url) => println!("{}", cli::normalize_domain(base_url)),
+                None => {
+                    for domain in &settings.domains {
+                        println!("{}", domain);
+                    }
+                }
+            }
+        }
+        DomainsCommands::Stats => {
+            let stats: DomainStats = match &settings.domain_stats_file {
+                Some(path) => cli::load_json(path)?,
+                None => DomainStats::default(),
+            };
+            cli::print_domain_stats(&stats, &settings.domains);
+        }
+        DomainsCommands::Add { entries } => {
+            for domain in entries {
+                let domain = cli::domain_from_argument(domain)?;
+                if store.add(&domain) {
+                    println!("Added {}", domain);
+                } else {
+                    println!("{} is already present", domain);
+                }
+            }
+            store.save()?;
         }
+        DomainsCommands::Remove { entries } => {
+            for domain in entries {
+                // entries that aren't urls may still be in the file as they were written
+                let domain = cli::domain_from_argument(domain).unwrap_or_else(|_| domain.clone());
+                if store.remove(&domain) {
+                    println!("Removed {}", domain);
+                } else {
+                    println!("{} is not present", domain);
+                }
+            }
+            store.save()?;
+        }
+    }
+    Ok(())
+}
+
+fn edit_cache(command: &CacheCommands, settings: &Settings) -> anyhow::Result<()> {
+    match command {
+        CacheCommands::List => {
+            if let Some(path) = &settings.resolution_cache_file {
+                println!("# resolution cache file: {}", path.display());
+                let cache: ResolutionCache = cli::load_json(path)?;
+                for (key, entry) in &cache.entries {
+                    let resolved_at = NaiveDateTime::from_timestamp_opt(entry.resolved_at, 0)
+                        .map(|time| time.to_string())
+                        .unwrap_or_default();
+                    println!(
+                        "{}\t{}{}/chunked/index-dvr.m3u8\tfetched {}",
+                        key, entry.domain, entry.url_path, resolved_at
+                    );
+                }
+            }
+            if let Some(path) = &settings.segment_cache_file {
+                let cache: SegmentCache = cli::load_json(path)?;
+                let num_valid = cache
+                    .segments
+                    .values()
+                    .filter(|record| record.is_valid())
+                    .count();
+                println!(
+                    "# segment cache file: {} ({} valid and {} dead segments)",
+                    path.display(),
+                    num_valid,
+                    cache.segments.len() - num_valid
+                );
+            }
+        }
+        CacheCommands::Clear => {
+            let paths = [
+                &settings.resolution_cache_file,
+                &settings.segment_cache_file,
+            ];
+            for path in paths.into_iter().flatten() {
+                if path.exists() {
+                    fs::remove_file(path)
+                        .with_context(|| format!("failed to remove {}", path.display()))?;
+                    println!("Removed {}", path.display());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints where the moment at `args.time` is in the VOD.
+/// A playlist file is only compared against the VOD's full playlist if its segments have no
+/// program date-times, since those already account for removed segments.
+async fn locate_moment(
+    args: AtArgs,
+    config: &Config,
+    token: &CancellationToken,
+) -> anyhow::Result<()> {
+    let path = Path::new(&args.vod);
+    let local_playlist = if path.exists() {
+        let contents =
+            fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Some(vods::decode_media_playlist_filter_nil_segments(
+            contents.into(),
+        )?)
+    } else {
+        None
+    };
+    let stamped = local_playlist.as_ref().is_some_and(|playlist| {
+        playlist
+            .segments
+            .iter()
+            .all(|segment| segment.program_date_time.is_some())
+    });
+    let (dwp, start_time) =
+        match &local_playlist {
+            Some(playlist) => {
+                let first = playlist
+                    .segments
+                    .first()
+                    .context("playlist has no segments")?;
+                match vods::url_to_domain_with_path(&first.uri) {
+                    Ok(dwp) => {
+                        learn_domain(&dwp.domain, config)?;
+                        let start_time = dwp.path.video_data.unix_time_seconds;
+                        (Some(dwp), start_time)
+                    }
+                    // A playlist written with the 'relative' transform still knows when the stream
+                    // started if its first segment is the first of the VOD and is stamped.
+                    Err(_) if stamped && vods::segment_sequence_number(&first.uri) == Some(0) => (
+                        None,
+                        first
+                            .program_date_time
+                            .context("playlist has no program date-times")?
+                            .naive_utc(),
+                    ),
+                    Err(err) => return Err(err.context(
+                        "playlist urls are not explicit VOD urls, and its first segment is not \
+                         the start of the VOD stamped with a program date-time; pass a VOD url \
+                         instead",
+                    )),
+                }
+            }
+            None => {
+                let dwp = resolve_vod_url(&args.vod, config)
+                    .context("not a playlist file or a VOD url")?;
+                let start_time = dwp.path.video_data.unix_time_seconds;
+                (Some(dwp), start_time)
+            }
+        };
+    let mut context = TransformContext {
+        start_time: Some(start_time),
+        ..TransformContext::default()
+    };
+    let mut full_playlist = None;
+    if let (false, Some(dwp)) = (stamped, &dwp) {
+        let client = build_client(config, token)?;
+        match dwp.get_m3u8_body(client, config.milliseconds_retry).await {
+            Ok(body) => {
+                let playlist = vods::decode_media_playlist_filter_nil_segments(body)?;
+                context = TransformContext::new(&playlist, dwp);
+                full_playlist = Some(playlist);
+            }
+            Err(err) if local_playlist.is_some() => println!(
+                "Failed to fetch {}, assuming that no segments were removed: {:#}",
+                dwp.get_index_dvr_url(),
+                err
+            ),
+            Err(err) => return Err(err),
+        }
+    }
+    let (playlist, player_target) = match local_playlist {
+        Some(playlist) => (playlist, path.display().to_string()),
+        None => (
+            full_playlist.context("the VOD's playlist was not fetched")?,
+            dwp.as_ref()
+                .context("the VOD's url is unknown")?
+                .get_index_dvr_url(),
+        ),
+    };
+    let time = vods::parse_utc_time(&args.time, start_time)?;
+    let position = vods::locate_time(&playlist, &context, time)?;
+    println!(
+        "{} UTC is {} into the VOD",
+        time,
+        duration_to_human_readable(&position.vod_offset)
+    );
+    let segment_name = position
+        .segment_uri
+        .rsplit('/')
+        .next()
+        .unwrap_or(&position.segment_uri);
+    match position.gap {
+        Some(gap) => println!(
+            "That part was removed from the playlist, the next segment is {} ({}), {} later",
+            position.segment_index,
+            segment_name,
+            duration_to_human_readable(&gap)
+        ),
+        None => println!(
+            "In segment {} ({}), {:.1}s in",
+            position.segment_index,
+            segment_name,
+            position.segment_offset.as_secs_f64()
+        ),
+    }
+    println!(
+        "Seek to {} in the playlist: mpv --start={} {}",
+        duration_to_human_readable(&position.playlist_offset),
+        position.playlist_offset.as_secs(),
+        player_target
+    );
+    if let Some(dwp) = dwp {
+        println!(
+            "On twitch.tv, if the VOD is still up: {}",
+            position.twitch_url(&dwp.path.video_data.video_id)
+        );
     }
-    write_media_playlist(&mediapl, dwp_and_body.dwp)?;
     Ok(())
 }
 
-struct Config {
-    concurrent_filter_invalid: usize,
-    client_timeout_milliseconds: u64,
-    milliseconds_retry: u64,
-    http2_keep_alive_timeout_milliseconds: u64,
-    http2_keep_alive_interval_milliseconds: u64,
+/// Serves the fake VODs until Ctrl-C is pressed.
+async fn run_fake_cdn(args: FakeCdnArgs, token: &CancellationToken) -> anyhow::Result<()> {
+    let fake_cdn: FakeCdnConfig = match &args.vods {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            toml::from_str(&contents)
+                .with_context(|| format!("invalid fake CDN file {}", path.display()))?
+        }
+        None => FakeCdnConfig::example(),
+    };
+    fake_cdn.validate()?;
+    let listener = TcpListener::bind(&args.listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", args.listen))?;
+    let base_url = format!("http://{}/", listener.local_addr()?);
+    println!("Serving {} VODs on {}", fake_cdn.vods.len(), base_url);
+    for vod in &fake_cdn.vods {
+        let domains = vod
+            .domains
+            .iter()
+            .map(|domain| format!("{}{}/", base_url, domain))
+            .collect::<Vec<_>>();
+        println!(
+            "{} {} started at {} on {}",
+            vod.streamer,
+            vod.video_id,
+            vod.start,
+            domains.join(", ")
+        );
+    }
+    if let Some(vod) = fake_cdn.vods.first() {
+        let domain_flags = fake_cdn
+            .domains
+            .iter()
+            .map(|domain| format!("--domain {}{}/", base_url, domain.name))
+            .collect::<Vec<_>>();
+        println!(
+            "Search with: vods {} --no-cache tt-manual-get-m3u8 --streamer {} --videoid {} --time '{}'",
+            domain_flags.join(" "),
+            vod.streamer,
+            vod.video_id,
+            vod.start
+        );
+    }
+    select! {
+        result = cli::serve_fake_cdn(Arc::new(fake_cdn), listener, args.log_requests) => result,
+        _ = token.cancelled() => Ok(()),
+    }
+}
+
+/// The first Ctrl-C stops all requests so that the partial results can be saved.
+/// The second one quits right away.
+async fn cancel_on_ctrl_c(token: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    eprintln!("\nStopping, press Ctrl-C again to quit without saving");
+    token.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(130);
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let (settings, config_path) = cli::resolve_settings(cli.config.as_deref(), cli.settings)?;
+    match &cli.command {
+        Commands::Config(ConfigCommands::Show) => {
+            return show_config(&settings, config_path.as_deref())
+        }
+        Commands::Domains(command) => return edit_domains(command, &settings),
+        Commands::Cache(command) => return edit_cache(command, &settings),
+        _ => {}
+    }
+    let has_base_url = settings.base_url.is_some();
+    let mut config = Config::try_from(settings)?;
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
-    let config: Config = Config {
-        concurrent_filter_invalid: 100,
-        client_timeout_milliseconds: 5000,
-        milliseconds_retry: 2000,
-        http2_keep_alive_timeout_milliseconds: 1000,
-        http2_keep_alive_interval_milliseconds: 500,
-    };
-    runtime.block_on(async {
+    let result = runtime.block_on(async {
+        let token = CancellationToken::new();
+        tokio::task::spawn(cancel_on_ctrl_c(token.clone()));
         match cli.command {
             Commands::TwitchTracker(args) => {
                 let twitch_data = TwitchTrackerData {
@@ -216,7 +1076,7 @@ fn main() -> anyhow::Result<()> {
                     video_id: args.video_id,
                 };
                 let video_data: VideoData = twitch_data.try_into()?;
-                main_helper(1, video_data, args.filter_invalid, &config).await?;
+                main_helper(1, video_data, None, &args.validation, &config, &token).await?;
             }
             Commands::StreamsCharts(args) => {
                 let sc_data = StreamsChartsData {
@@ -225,7 +1085,7 @@ fn main() -> anyhow::Result<()> {
                     video_id: args.video_id,
                 };
                 let video_data: VideoData = sc_data.try_into()?;
-                main_helper(60, video_data, args.filter_invalid, &config).await?;
+                main_helper(60, video_data, None, &args.validation, &config, &token).await?;
             }
             Commands::SullyGnome(args) => {
                 let twitch_data = SullyGnomeData {
@@ -234,9 +1094,27 @@ fn main() -> anyhow::Result<()> {
                     video_id: args.video_id,
                 };
                 let video_data: VideoData = twitch_data.try_into()?;
-                main_helper(1, video_data, args.filter_invalid, &config).await?;
+                main_helper(1, video_data, None, &args.validation, &config, &token).await?;
+            }
+            Commands::Url(args) => {
+                let dwp = resolve_vod_url(&args.url, &config)?;
+                if !has_base_url && !config.domains.contains(&dwp.domain) {
+                    config.domains.insert(0, Arc::clone(&dwp.domain));
+                }
+                let video_data = dwp.path.video_data.with_offset(0);
+                main_helper(1, video_data, Some(dwp), &args.validation, &config, &token).await?;
             }
+            Commands::Check(args) => check_playlist(args, &config, &token).await?,
+            Commands::Monitor(args) => monitor_archive(args, &config, &token).await?,
+            Commands::At(args) => locate_moment(args, &config, &token).await?,
+            Commands::FakeCdn(args) => run_fake_cdn(args, &token).await?,
+            Commands::Config(_) | Commands::Domains(_) | Commands::Cache(_) => {}
         }
         Ok(())
-    })
+    });
+    // the recording is most useful when the search failed, so it is written either way
+    if let (Some(recorder), Some(path)) = (&config.har_recorder, &config.record_file) {
+        cli::save_json(path, &recorder.to_har())?;
+    }
+    result
 }
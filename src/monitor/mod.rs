@@ -0,0 +1,126 @@
+mod tests;
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How many checks of each playlist are kept; older ones are dropped so the history file
+/// doesn't grow forever.
+pub const MAX_RECORDS_PER_PLAYLIST: usize = 100;
+
+/// Twitch deletes VODs after 7, 14 or 60 days, depending on the streamer.
+pub const RETENTION_DAYS: [i64; 3] = [7, 14, 60];
+
+/// Availability of every monitored playlist over time, keyed by canonical playlist path.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MonitorHistory {
+    pub playlists: BTreeMap<String, Vec<AvailabilityRecord>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AvailabilityRecord {
+    /// Unix time of the check.
+    pub checked_at: i64,
+    pub checked: usize,
+    pub valid: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AvailabilityAlert {
+    /// The share of valid segments fell by more than the threshold since the previous check.
+    Dropped { from: f64, to: f64 },
+    /// The VOD is within the warning window of a typical retention age.
+    NearRetention { age_days: i64, limit_days: i64 },
+    /// The VOD has outlived the longest typical retention age since the previous check.
+    /// This is only reported once, by the first check after the VOD got that old.
+    PastRetention { age_days: i64, limit_days: i64 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AlertThresholds {
+    /// Smallest fall in availability, between 0 and 1, that is reported.
+    pub drop: f64,
+    pub retention_warning_days: i64,
+}
+
+impl Default for AlertThresholds {
+    fn default() -> Self {
+        Self {
+            drop: 0.05,
+            retention_warning_days: 2,
+        }
+    }
+}
+
+impl AvailabilityRecord {
+    pub fn availability(&self) -> f64 {
+        if self.checked == 0 {
+            return 0.0;
+        }
+        self.valid as f64 / self.checked as f64
+    }
+}
+
+impl MonitorHistory {
+    pub fn record(&mut self, playlist: &str, record: AvailabilityRecord) {
+        let records = self.playlists.entry(playlist.to_string()).or_default();
+        records.push(record);
+        let excess = records.len().saturating_sub(MAX_RECORDS_PER_PLAYLIST);
+        records.drain(..excess);
+    }
+
+    /// Compares the last two checks of `playlist` and looks at the age of the VOD, which is
+    /// `vod_age` at the last check.
+    pub fn alerts(
+        &self,
+        playlist: &str,
+        vod_age: chrono::Duration,
+        thresholds: &AlertThresholds,
+    ) -> Vec<AvailabilityAlert> {
+        let mut alerts = vec![];
+        let mut previous_age_days = None;
+        if let Some([.., previous, latest]) = self.playlists.get(playlist).map(Vec::as_slice) {
+            let (from, to) = (previous.availability(), latest.availability());
+            if from - to > thresholds.drop {
+                alerts.push(AvailabilityAlert::Dropped { from, to });
+            }
+            let since_previous = chrono::Duration::seconds(latest.checked_at - previous.checked_at);
+            previous_age_days = Some((vod_age - since_previous).num_days());
+        }
+        let age_days = vod_age.num_days();
+        let last_limit_days = RETENTION_DAYS[RETENTION_DAYS.len() - 1];
+        match RETENTION_DAYS
+            .iter()
+            .copied()
+            .find(|limit| age_days < *limit)
+        {
+            Some(limit_days) if limit_days - age_days <= thresholds.retention_warning_days => {
+                alerts.push(AvailabilityAlert::NearRetention {
+                    age_days,
+                    limit_days,
+                });
+            }
+            Some(_) => {}
+            None if previous_age_days.is_none_or(|days| days < last_limit_days) => {
+                alerts.push(AvailabilityAlert::PastRetention {
+                    age_days,
+                    limit_days: last_limit_days,
+                });
+            }
+            None => {}
+        }
+        alerts
+    }
+}
+
+/// Picks `count` indices out of `0..len` spread evenly from the first to the last.
+/// Every index is returned if `count` is 0 or at least `len`.
+pub fn evenly_spaced_indices(len: usize, count: usize) -> Vec<usize> {
+    if count == 0 || count >= len {
+        return (0..len).collect();
+    }
+    if count == 1 {
+        return vec![0];
+    }
+    (0..count).map(|i| i * (len - 1) / (count - 1)).collect()
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_evenly_spaced_indices() {
+    assert_eq!(evenly_spaced_indices(10, 0), (0..10).collect::<Vec<_>>());
+    assert_eq!(evenly_spaced_indices(3, 5), vec![0, 1, 2]);
+    assert_eq!(evenly_spaced_indices(10, 1), vec![0]);
+    assert_eq!(evenly_spaced_indices(10, 4), vec![0, 3, 6, 9]);
+}
+
+#[test]
+fn test_alerts() {
+    let mut history = MonitorHistory::default();
+    let thresholds = AlertThresholds::default();
+    let young = chrono::Duration::days(1);
+    let record = |valid| AvailabilityRecord {
+        checked_at: 0,
+        checked: 10,
+        valid,
+    };
+    history.record("a.m3u8", record(10));
+    assert!(history.alerts("a.m3u8", young, &thresholds).is_empty());
+    history.record("a.m3u8", record(10));
+    assert!(history.alerts("a.m3u8", young, &thresholds).is_empty());
+    history.record("a.m3u8", record(6));
+    assert_eq!(
+        history.alerts("a.m3u8", young, &thresholds),
+        vec![AvailabilityAlert::Dropped { from: 1.0, to: 0.6 }]
+    );
+
+    assert_eq!(
+        history.alerts("b.m3u8", chrono::Duration::days(13), &thresholds),
+        vec![AvailabilityAlert::NearRetention {
+            age_days: 13,
+            limit_days: 14
+        }]
+    );
+    assert!(history
+        .alerts("b.m3u8", chrono::Duration::days(30), &thresholds)
+        .is_empty());
+    assert_eq!(
+        history.alerts("b.m3u8", chrono::Duration::days(90), &thresholds),
+        vec![AvailabilityAlert::PastRetention {
+            age_days: 90,
+            limit_days: 60
+        }]
+    );
+}
+
+#[test]
+fn test_past_retention_is_reported_once() {
+    let mut history = MonitorHistory::default();
+    let thresholds = AlertThresholds::default();
+    let day = 24 * 60 * 60;
+    let record = |checked_at| AvailabilityRecord {
+        checked_at,
+        checked: 10,
+        valid: 10,
+    };
+    history.record("a.m3u8", record(0));
+    history.record("a.m3u8", record(day));
+    // the VOD turned 60 days old between the checks
+    assert_eq!(
+        history.alerts("a.m3u8", chrono::Duration::days(60), &thresholds),
+        vec![AvailabilityAlert::PastRetention {
+            age_days: 60,
+            limit_days: 60
+        }]
+    );
+    history.record("a.m3u8", record(2 * day));
+    assert!(history
+        .alerts("a.m3u8", chrono::Duration::days(61), &thresholds)
+        .is_empty());
+}
+
+#[test]
+fn test_record_keeps_the_latest_checks() {
+    let mut history = MonitorHistory::default();
+    for checked_at in 0..MAX_RECORDS_PER_PLAYLIST as i64 + 5 {
+        let record = AvailabilityRecord {
+            checked_at,
+            checked: 10,
+            valid: 10,
+        };
+        history.record("a.m3u8", record);
+    }
+    let records = &history.playlists["a.m3u8"];
+    assert_eq!(records.len(), MAX_RECORDS_PER_PLAYLIST);
+    assert_eq!(records[0].checked_at, 5);
+    assert_eq!(
+        records.last().unwrap().checked_at,
+        MAX_RECORDS_PER_PLAYLIST as i64 + 4
+    );
+}
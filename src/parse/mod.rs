@@ -10,19 +10,24 @@ mod tests;
 use anyhow::{anyhow, Context};
 use bytes::{Buf, Bytes};
 use chrono::{NaiveDateTime, Timelike};
-use futures::Future;
+use futures::{Future, Stream, StreamExt};
 use m3u8_rs::{MediaPlaylist, MediaSegment};
-use reqwest::Client;
 use sha1::{Digest, Sha1};
 use std::{
     fmt::Display,
-    io::{stdout, Write},
+    pin::Pin,
     sync::Arc,
-    time::Duration,
+    task::{self, Poll},
+    time::{Duration, Instant},
 };
 use tokio::{select, sync::mpsc};
 use url::Url;
 
+use crate::{
+    is_fatal, AimdConfig, AimdController, Event, HeadStart, HttpClient, RequestOutcome,
+    SegmentCache, SegmentCachePolicy, SegmentRecord,
+};
+
 pub const DOMAINS: [&str; 12] = [
     "https://vod-secure.twitch.tv/",
     "https://vod-metro.twitch.tv/",
@@ -65,14 +70,23 @@ pub struct ValidDwpResponse<T: Clone + 'static + Send + Display> {
     pub body: Bytes,
 }
 
-async fn retry_on_error<F, T, E, Fut>(doer: F, milliseconds_retry: u64) -> Result<T, E>
+pub struct ValidSegmentsResponse {
+    pub playlist: MediaPlaylist,
+    pub num_checked: usize,
+    /// Set if validation stopped before every segment was checked.
+    /// In that case `playlist` only contains the segments found to be valid so far.
+    pub error: Option<anyhow::Error>,
+}
+
+async fn retry_on_error<F, T, Fut>(doer: F, milliseconds_retry: u64) -> anyhow::Result<T>
 where
     F: (Fn() -> Fut) + Clone,
-    Fut: Future<Output = Result<T, E>>,
+    Fut: Future<Output = anyhow::Result<T>>,
 {
     let result = doer().await;
     match result {
         Ok(good) => Ok(good),
+        Err(err) if is_fatal(&err) => Err(err),
         Err(_) => {
             if milliseconds_retry > 0 {
                 tokio::time::sleep(Duration::from_millis(milliseconds_retry)).await;
@@ -123,8 +137,12 @@ pub fn url_to_domain_with_path(url_str: &str) -> anyhow::Result<DomainWithPath<A
         .context("url is not valid")?
         .to_string();
     let video_data = Arc::new(url_path_to_video_data(&main_part)?);
+    let port = parsed
+        .port()
+        .map(|port| format!(":{}", port))
+        .unwrap_or_default();
     let result = DomainWithPath {
-        domain: Arc::new(format!("{}://{}/", parsed.scheme(), host)),
+        domain: Arc::new(format!("{}://{}{}/", parsed.scheme(), host, port)),
         path: Arc::new(VideoPath {
             url_path: main_part,
             video_data,
@@ -187,12 +205,12 @@ impl VideoData {
         }
     }
 
-    pub fn get_domain_with_paths_list(
+    pub fn get_domain_with_paths_list<T: Clone + 'static + Send + Display>(
         &self,
-        domains: &[&'static str],
+        domains: &[T],
         seconds: i64,
         to_unix: bool,
-    ) -> Vec<DomainWithPaths<&'static str>> {
+    ) -> Vec<DomainWithPaths<T>> {
         let video_paths = (0..seconds)
             .map(|i| Arc::new(self.with_offset(i).get_video_path(to_unix)))
             .collect::<Vec<_>>();
@@ -200,7 +218,7 @@ impl VideoData {
         domains
             .iter()
             .map(|domain| DomainWithPaths {
-                domain: *domain,
+                domain: domain.clone(),
                 paths: Arc::clone(&video_paths),
             })
             .collect::<Vec<_>>()
@@ -222,7 +240,22 @@ impl<T: Clone + 'static + Send + Display + Sync> DomainWithPaths<T> {
     /// If all of the results are errors, it returns the last error.
     pub async fn get_first_valid_dwp(
         &self,
-        client: Client,
+        client: HttpClient,
+        milliseconds_retry: u64,
+    ) -> anyhow::Result<ValidDwpResponse<T>> {
+        let result = self
+            .get_first_valid_dwp_helper(HttpClient::clone(&client), milliseconds_retry)
+            .await;
+        client.emit(Event::DomainResult {
+            domain: self.domain.to_string(),
+            found: result.is_ok(),
+        });
+        result
+    }
+
+    async fn get_first_valid_dwp_helper(
+        &self,
+        client: HttpClient,
         milliseconds_retry: u64,
     ) -> anyhow::Result<ValidDwpResponse<T>> {
         let mut domain_with_path_list = self.to_list_of_domain_with_path();
@@ -230,7 +263,7 @@ impl<T: Clone + 'static + Send + Display + Sync> DomainWithPaths<T> {
         // establish TCP connection for reuse
         // https://groups.google.com/g/golang-nuts/c/5T5aiDRl_cw/m/zYPGtCOYBwAJ
         let body = last
-            .get_m3u8_body(Client::clone(&client), milliseconds_retry)
+            .get_m3u8_body(HttpClient::clone(&client), milliseconds_retry)
             .await;
         match body {
             Ok(body) => Ok(ValidDwpResponse { dwp: last, body }),
@@ -238,7 +271,7 @@ impl<T: Clone + 'static + Send + Display + Sync> DomainWithPaths<T> {
                 // reuse with other requests
                 let items = domain_with_path_list
                     .into_iter()
-                    .map(move |item| (item, Client::clone(&client)));
+                    .map(move |item| (item, HttpClient::clone(&client)));
                 let response =
                     first_ok::get_first_ok_bounded(items, 0, move |(item, client)| async move {
                         let body = item.get_m3u8_body(client, milliseconds_retry).await?;
@@ -256,17 +289,26 @@ impl<T: Clone + 'static + Send + Display + Sync> DomainWithPaths<T> {
 
 /// If the list of items is empty, it returns `None`.
 /// If all of the results are errors, it returns the last error.
+/// The domains are probed in the order given, with `head_start` holding back all but the first few.
 pub async fn get_first_valid_dwp<T: Clone + 'static + Send + Display + Sync>(
     domain_with_paths_list: Vec<DomainWithPaths<T>>,
-    client: Client,
+    client: HttpClient,
     milliseconds_retry: u64,
+    head_start: HeadStart,
 ) -> Option<anyhow::Result<ValidDwpResponse<T>>> {
     first_ok::get_first_ok_bounded(
         domain_with_paths_list
             .into_iter()
-            .map(move |item| (item, Client::clone(&client))),
+            .enumerate()
+            .map(move |(rank, item)| (rank, item, HttpClient::clone(&client))),
         0,
-        move |(item, client)| async move { item.get_first_valid_dwp(client, milliseconds_retry).await },
+        move |(rank, item, client)| async move {
+            let delay = head_start.delay_for(rank);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            item.get_first_valid_dwp(client, milliseconds_retry).await
+        },
     )
     .await
 }
@@ -301,20 +343,19 @@ impl<T: Clone + 'static + Send + Display> DomainWithPath<T> {
 
     pub async fn get_m3u8_body(
         &self,
-        client: Client,
+        client: HttpClient,
         milliseconds_retry: u64,
     ) -> anyhow::Result<Bytes> {
         let url = Arc::new(self.get_index_dvr_url());
-        let response = retry_on_error(
-            || async { client.get(url.as_ref()).send().await },
-            milliseconds_retry,
-        )
-        .await?;
-        let status_code = response.status().as_u16();
+        client.emit(Event::CandidateTried {
+            url: url.to_string(),
+        });
+        let response = retry_on_error(|| client.get(url.as_ref()), milliseconds_retry).await?;
+        let status_code = response.status();
         if status_code != 200 {
             return Err(anyhow!(format!("status code is {}", status_code)));
         }
-        let bytes = response.bytes().await?;
+        let bytes = client.bytes(response).await?;
         Ok(bytes)
     }
 }
@@ -345,113 +386,356 @@ pub fn get_media_playlist_duration(playlist: &MediaPlaylist) -> Duration {
     Duration::from_secs_f64(duration)
 }
 
+/// Segments that `segment_cache` trusts under `policy` aren't requested again.
+/// The results of the new checks are added to `segment_cache`.
 pub async fn get_media_playlist_with_valid_segments(
     mut raw_playlist: MediaPlaylist,
-    concurrent: usize,
-    client: Client,
+    concurrency: &AimdConfig,
+    client: HttpClient,
     milliseconds_retry: u64,
-) -> MediaPlaylist {
-    raw_playlist.segments = get_valid_segments(
-        raw_playlist.segments,
-        concurrent,
+    segment_cache: &mut SegmentCache,
+    policy: &SegmentCachePolicy,
+) -> ValidSegmentsResponse {
+    let urls = raw_playlist
+        .segments
+        .iter()
+        .map(|segment| String::clone(&segment.uri))
+        .collect::<Vec<_>>();
+    let valid_indices = get_valid_indices(
+        urls,
+        concurrency,
         client,
         milliseconds_retry,
+        segment_cache,
+        policy,
     )
     .await;
-    raw_playlist
-}
-
-async fn get_valid_segments(
-    segments: Vec<MediaSegment>,
-    concurrent: usize,
-    client: Client,
-    milliseconds_retry: u64,
-) -> Vec<MediaSegment> {
-    let urls = segments
-        .iter()
-        .map(|segment| String::clone(&segment.uri))
-        .collect::<Vec<_>>();
-    let index_is_valid = get_valid_indices(urls, concurrent, client, milliseconds_retry).await;
-    segments
+    raw_playlist.segments = raw_playlist
+        .segments
         .into_iter()
         .enumerate()
-        .filter_map(|(i, elem)| if index_is_valid[i] { Some(elem) } else { None })
-        .collect()
+        .filter_map(|(i, elem)| {
+            if valid_indices.is_valid[i] {
+                Some(elem)
+            } else {
+                None
+            }
+        })
+        .collect();
+    ValidSegmentsResponse {
+        playlist: raw_playlist,
+        num_checked: valid_indices.num_checked,
+        error: valid_indices.error,
+    }
 }
 
-static CLEAR_LINE: &str = "\x1b[2K";
+struct ValidIndices {
+    is_valid: Vec<bool>,
+    num_checked: usize,
+    error: Option<anyhow::Error>,
+}
 
-async fn get_valid_indices(
-    urls: Vec<String>,
-    concurrent: usize,
-    client: Client,
+/// What is known about a segment after checking it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SegmentCheck {
+    Cached(bool),
+    Responded(SegmentRecord),
+    NoResponse,
+}
+
+impl SegmentCheck {
+    pub(crate) fn is_valid(&self) -> bool {
+        match self {
+            SegmentCheck::Cached(valid) => *valid,
+            SegmentCheck::Responded(record) => record.is_valid(),
+            SegmentCheck::NoResponse => false,
+        }
+    }
+
+    /// Whether the check can be trusted, see `SegmentRecord::is_definitive`.
+    /// A timeout might also turn out differently on the next try.
+    pub(crate) fn is_definitive(&self) -> bool {
+        match self {
+            SegmentCheck::Cached(_) => true,
+            SegmentCheck::Responded(record) => record.is_definitive(),
+            SegmentCheck::NoResponse => false,
+        }
+    }
+}
+
+/// What a segment url answered with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SegmentStatus {
+    /// `None` if there was no response, even after retrying, e.g. because of a timeout.
+    pub status: Option<u16>,
+    /// The content length, if the response had one.
+    pub size: Option<u64>,
+    /// The time from sending the request until the response or the error, including retries.
+    pub latency: Duration,
+}
+
+impl SegmentStatus {
+    pub fn is_valid(&self) -> bool {
+        self.status == Some(200)
+    }
+
+    /// The record to keep in the segment cache, if there was a response that can be trusted.
+    pub fn to_record(&self, checked_at: i64) -> Option<SegmentRecord> {
+        let record = SegmentRecord {
+            status: self.status?,
+            size: self.size,
+            checked_at,
+        };
+        record.is_definitive().then_some(record)
+    }
+}
+
+enum CheckResult {
+    Checked(usize, SegmentStatus),
+    Aborted(anyhow::Error),
+}
+
+/// The statuses of the segments being checked by `check_segments`, in the order that they complete.
+/// If checking has to stop early, e.g. because the request budget ran out, the last item is the
+/// error. Dropping the stream stops the checks that are still pending.
+pub struct SegmentStatusStream {
+    receiver: mpsc::Receiver<CheckResult>,
+    controller: Arc<AimdController>,
+    finished: bool,
+}
+
+impl SegmentStatusStream {
+    /// The current concurrency limit.
+    pub fn concurrency_limit(&self) -> usize {
+        self.controller.limit()
+    }
+}
+
+impl Stream for SegmentStatusStream {
+    type Item = anyhow::Result<(usize, SegmentStatus)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        self.receiver.poll_recv(cx).map(|result| match result {
+            Some(CheckResult::Checked(index, status)) => Some(Ok((index, status))),
+            Some(CheckResult::Aborted(err)) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+            None => None,
+        })
+    }
+}
+
+/// Checks the segments at `indices` of `urls` concurrently, adjusting the concurrency with `concurrency`,
+/// and yields each result as soon as it is known.
+/// Must be called from within a tokio runtime since the checks run as background tasks.
+///
+/// ```no_run
+/// # async fn run(urls: Vec<String>, client: vods::HttpClient) {
+/// use futures::StreamExt;
+///
+/// let indices = (0..urls.len()).collect();
+/// let mut statuses = vods::check_segments(
+///     std::sync::Arc::new(urls),
+///     indices,
+///     &vods::AimdConfig::default(),
+///     client,
+///     2000,
+/// );
+/// while let Some(Ok((index, status))) = statuses.next().await {
+///     println!("segment {} answered {:?} after {:?}", index, status.status, status.latency);
+/// }
+/// # }
+/// ```
+pub fn check_segments(
+    urls: Arc<Vec<String>>,
+    indices: Vec<usize>,
+    concurrency: &AimdConfig,
+    client: HttpClient,
     milliseconds_retry: u64,
-) -> Vec<bool> {
-    let urls = Arc::new(urls);
-    let (valid_indices_sender, mut valid_indices_receiver) = mpsc::channel::<Option<usize>>(1);
+) -> SegmentStatusStream {
+    let controller = AimdController::new(concurrency.clone());
+    let (check_results_sender, check_results_receiver) = mpsc::channel::<CheckResult>(1);
     let (request_indices_sender, request_indices_receiver) = async_channel::bounded::<usize>(1);
-    for _ in 0..concurrent {
+    // spawn enough workers for the maximum limit; the controller decides how many may run
+    for _ in 0..controller.max().min(indices.len()) {
         let request_indices_receiver = async_channel::Receiver::clone(&request_indices_receiver);
         let urls = Arc::clone(&urls);
-        let client = Client::clone(&client);
-        let valid_indices_sender = mpsc::Sender::clone(&valid_indices_sender);
+        let client = HttpClient::clone(&client);
+        let controller = Arc::clone(&controller);
+        let check_results_sender = mpsc::Sender::clone(&check_results_sender);
         tokio::task::spawn(async move {
             let task = || async {
-                while let Ok(request_index) = request_indices_receiver.recv().await {
+                loop {
+                    let permit = controller.acquire().await;
+                    let Ok(request_index) = request_indices_receiver.recv().await else {
+                        return;
+                    };
                     let url = &urls[request_index];
-                    let client = Client::clone(&client);
-                    let result = if url_is_valid(url, client, milliseconds_retry).await {
-                        Some(request_index)
-                    } else {
-                        None
+                    let client = HttpClient::clone(&client);
+                    let result = match check_url(url, client, milliseconds_retry).await {
+                        Ok((status, outcome)) => {
+                            permit.finish(outcome);
+                            CheckResult::Checked(request_index, status)
+                        }
+                        Err(err) => CheckResult::Aborted(err),
                     };
-                    if valid_indices_sender.send(result).await.is_err() {
+                    if check_results_sender.send(result).await.is_err() {
                         return;
                     };
                 }
             };
             select! {
                 _ = task() => {}
-                _ = valid_indices_sender.closed() => {}
+                _ = check_results_sender.closed() => {}
             }
         });
     }
-    tokio::task::spawn({
-        let urls = Arc::clone(&urls);
-        async move {
-            for i in 0..urls.len() {
-                if request_indices_sender.send(i).await.is_err() {
-                    return;
-                }
+    tokio::task::spawn(async move {
+        for i in indices {
+            if request_indices_sender.send(i).await.is_err() {
+                return;
             }
         }
     });
-    let mut done_count = 0;
-    let mut result = vec![false; urls.len()];
-    for _ in &*urls {
-        if let Some(response) = valid_indices_receiver.recv().await {
-            done_count += 1;
-            print!("{}", CLEAR_LINE);
-            print!("\r");
-            print!("Processed {} segments out of {}", done_count, urls.len());
-            let _ = stdout().flush();
-            if let Some(index) = response {
-                result[index] = true;
-            }
-        }
+    SegmentStatusStream {
+        receiver: check_results_receiver,
+        controller,
+        finished: false,
     }
-    println!();
-    result
 }
 
-async fn url_is_valid(url: &String, client: Client, milliseconds_retry: u64) -> bool {
-    let response = retry_on_error(
-        || async { client.get(url).send().await },
+async fn get_valid_indices(
+    urls: Vec<String>,
+    concurrency: &AimdConfig,
+    client: HttpClient,
+    milliseconds_retry: u64,
+    segment_cache: &mut SegmentCache,
+    policy: &SegmentCachePolicy,
+) -> ValidIndices {
+    let indices = (0..urls.len()).collect::<Vec<_>>();
+    let mut is_valid = vec![false; urls.len()];
+    let (checks, error) = check_indices(
+        &Arc::new(urls),
+        indices,
+        concurrency,
+        client,
         milliseconds_retry,
+        segment_cache,
+        policy,
     )
     .await;
+    for (index, check) in &checks {
+        is_valid[*index] = check.is_valid();
+    }
+    ValidIndices {
+        is_valid,
+        num_checked: checks.len(),
+        error,
+    }
+}
+
+/// Checks the segments at `indices` concurrently and returns what was found, in no particular order.
+/// The error is set if checking stopped early.
+pub(crate) async fn check_indices(
+    urls: &Arc<Vec<String>>,
+    indices: Vec<usize>,
+    concurrency: &AimdConfig,
+    client: HttpClient,
+    milliseconds_retry: u64,
+    segment_cache: &mut SegmentCache,
+    policy: &SegmentCachePolicy,
+) -> (Vec<(usize, SegmentCheck)>, Option<anyhow::Error>) {
+    let now = chrono::Utc::now().timestamp();
+    let num_total = indices.len();
+    let mut checks = vec![];
+    let mut pending = vec![];
+    for index in indices {
+        match segment_cache.lookup(&urls[index], policy, now) {
+            Some(valid) => checks.push((index, SegmentCheck::Cached(valid))),
+            None => pending.push(index),
+        }
+    }
+    let mut done_count = checks.len();
+    if done_count > 0 {
+        client.emit(Event::SegmentsCached {
+            count: done_count,
+            total: num_total,
+        });
+    }
+    let mut statuses = check_segments(
+        Arc::clone(urls),
+        pending,
+        concurrency,
+        HttpClient::clone(&client),
+        milliseconds_retry,
+    );
+    let mut error = None;
+    while let Some(result) = statuses.next().await {
+        let (index, status) = match result {
+            Ok(checked) => checked,
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        };
+        done_count += 1;
+        let checked_at = chrono::Utc::now().timestamp();
+        if let Some(record) = status.to_record(checked_at) {
+            segment_cache.record(&urls[index], record);
+        }
+        let check = match status.status {
+            Some(code) => SegmentCheck::Responded(SegmentRecord {
+                status: code,
+                size: status.size,
+                checked_at,
+            }),
+            None => SegmentCheck::NoResponse,
+        };
+        checks.push((index, check));
+        client.emit(Event::SegmentChecked {
+            index,
+            valid: check.is_valid(),
+            done: done_count,
+            total: num_total,
+            concurrency: statuses.concurrency_limit(),
+        });
+    }
+    (checks, error)
+}
+
+/// Returns what the url responded with and how the request should count towards the
+/// concurrency limit.
+/// An error is only returned if no more requests should be made at all.
+async fn check_url(
+    url: &str,
+    client: HttpClient,
+    milliseconds_retry: u64,
+) -> anyhow::Result<(SegmentStatus, RequestOutcome)> {
+    let start = Instant::now();
+    let response = retry_on_error(|| client.get(url), milliseconds_retry).await;
+    let latency = start.elapsed();
     match response {
-        Ok(response) => response.status().as_u16() == 200,
-        Err(_) => false,
+        Ok(response) => {
+            let status_code = response.status();
+            let status = SegmentStatus {
+                status: Some(status_code),
+                size: response.content_length(),
+                latency,
+            };
+            Ok((status, RequestOutcome::from_status(status_code, latency)))
+        }
+        Err(err) if is_fatal(&err) => Err(err),
+        Err(err) => {
+            let status = SegmentStatus {
+                status: None,
+                size: None,
+                latency,
+            };
+            Ok((status, RequestOutcome::from_anyhow_error(&err)))
+        }
     }
 }
@@ -32,6 +32,13 @@ fn test_url_to_domain_with_path() {
     );
 }
 
+#[test]
+fn test_url_to_domain_with_path_keeps_port() {
+    let url = "http://127.0.0.1:8080/c5992ececce7bd7d350d_gmhikaru_47198535725_1664038929/chunked/index-dvr.m3u8";
+    let result = url_to_domain_with_path(url).unwrap();
+    assert_eq!(*result.domain, "http://127.0.0.1:8080/");
+}
+
 #[test]
 fn test_video_data_to_string() {
     let url_path = "c5992ececce7bd7d350d_gmhikaru_47198535725_1664038929";
@@ -55,3 +62,110 @@ fn test_get_url_path() {
         assert_eq!(video_data.get_url_path(true), url_path);
     }
 }
+
+#[tokio::test]
+async fn test_check_segments_streams_statuses() {
+    let cdn = crate::FakeCdn::new();
+    cdn.serve("https://a.invalid/0.ts", vec![0; 3]);
+    cdn.serve("https://a.invalid/2.ts", vec![0; 5]);
+    cdn.insert(
+        "https://a.invalid/3.ts",
+        crate::FakeResponse::ConnectionFailed,
+    );
+    let client = HttpClient::with_transport(cdn, &crate::RateLimitConfig::default());
+    let urls = (0..4)
+        .map(|i| format!("https://a.invalid/{}.ts", i))
+        .collect::<Vec<_>>();
+    let mut statuses = check_segments(
+        Arc::new(urls),
+        vec![0, 1, 2, 3],
+        &AimdConfig::default(),
+        client,
+        0,
+    )
+    .map(|result| result.unwrap())
+    .collect::<Vec<_>>()
+    .await;
+    statuses.sort_by_key(|(index, _)| *index);
+    let summary = statuses
+        .iter()
+        .map(|(index, status)| (*index, status.status, status.size))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (0, Some(200), Some(3)),
+            (1, Some(404), Some(0)),
+            (2, Some(200), Some(5)),
+            (3, None, None),
+        ]
+    );
+    assert!(statuses[0].1.is_valid());
+    assert!(!statuses[3].1.is_valid());
+}
+
+#[tokio::test]
+async fn test_check_segments_ends_with_fatal_error() {
+    let cdn = crate::FakeCdn::new();
+    let rate_limit = crate::RateLimitConfig {
+        max_requests: Some(2),
+        ..Default::default()
+    };
+    let client = HttpClient::with_transport(cdn, &rate_limit);
+    let urls = (0..10)
+        .map(|i| format!("https://a.invalid/{}.ts", i))
+        .collect::<Vec<_>>();
+    let results = check_segments(
+        Arc::new(urls),
+        (0..10).collect(),
+        &AimdConfig::default(),
+        client,
+        0,
+    )
+    .collect::<Vec<_>>()
+    .await;
+    let (last, checked) = results.split_last().unwrap();
+    assert!(is_fatal(last.as_ref().unwrap_err()));
+    assert!(checked.iter().all(|result| result.is_ok()));
+    assert_eq!(checked.len(), 2);
+}
+
+#[tokio::test]
+async fn test_check_indices_only_caches_definitive_statuses() {
+    let cdn = crate::FakeCdn::new();
+    cdn.serve("https://a.invalid/0.ts", vec![0; 3]);
+    cdn.insert(
+        "https://a.invalid/2.ts",
+        crate::FakeResponse::Status(503, Bytes::new()),
+    );
+    let client = HttpClient::with_transport(cdn, &crate::RateLimitConfig::default());
+    let urls = (0..3)
+        .map(|i| format!("https://a.invalid/{}.ts", i))
+        .collect::<Vec<_>>();
+    let mut segment_cache = SegmentCache::default();
+    let (mut checks, error) = check_indices(
+        &Arc::new(urls),
+        vec![0, 1, 2],
+        &AimdConfig::default(),
+        client,
+        0,
+        &mut segment_cache,
+        &SegmentCachePolicy::default(),
+    )
+    .await;
+    assert!(error.is_none());
+    checks.sort_by_key(|(index, _)| *index);
+    assert!(checks[0].1.is_definitive());
+    assert!(checks[1].1.is_definitive());
+    assert!(!checks[2].1.is_valid());
+    assert!(!checks[2].1.is_definitive());
+    assert!(segment_cache
+        .segments
+        .contains_key("https://a.invalid/0.ts"));
+    assert!(segment_cache
+        .segments
+        .contains_key("https://a.invalid/1.ts"));
+    assert!(!segment_cache
+        .segments
+        .contains_key("https://a.invalid/2.ts"));
+}
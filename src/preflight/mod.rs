@@ -0,0 +1,59 @@
+mod tests;
+
+use std::fmt::Display;
+
+use crate::{is_connection_error, is_fatal, HttpClient};
+
+/// The result of checking which domains can be reached at all.
+pub struct Preflight<T> {
+    pub reachable: Vec<T>,
+    pub unreachable: Vec<(T, anyhow::Error)>,
+}
+
+/// Sends one request to the root of every domain concurrently.
+/// This resolves DNS and opens a connection that the path candidates can reuse afterwards,
+/// so the body is read too to hand the connection back to the pool.
+/// Any HTTP response, even a 403 or 404, counts as reachable, and any error as unreachable.
+/// A failure to resolve, connect or get a response in time is retried once first.
+/// The order of `domains` is kept in `reachable`.
+///
+/// Fails if the client was cancelled or its request budget ran out, since the domains that
+/// weren't probed yet would otherwise look unreachable.
+pub async fn preflight_domains<T: Clone + Display>(
+    domains: &[T],
+    client: &HttpClient,
+) -> anyhow::Result<Preflight<T>> {
+    let results = futures::future::join_all(
+        domains
+            .iter()
+            .map(|domain| async move { (domain, probe(&domain.to_string(), client).await) }),
+    )
+    .await;
+    let mut preflight = Preflight {
+        reachable: vec![],
+        unreachable: vec![],
+    };
+    for (domain, result) in results {
+        match result {
+            Ok(()) => preflight.reachable.push(domain.clone()),
+            Err(err) if is_fatal(&err) => return Err(err),
+            Err(err) => preflight.unreachable.push((domain.clone(), err)),
+        }
+    }
+    Ok(preflight)
+}
+
+async fn probe(url: &str, client: &HttpClient) -> anyhow::Result<()> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match client.get(url).await {
+            Ok(response) => {
+                let _ = client.bytes(response).await;
+                return Ok(());
+            }
+            Err(err) if is_connection_error(&err) && attempts < 2 => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::{
+    BudgetExhausted, CancellationToken, Cancelled, ConnectionFailed, FakeCdn, FakeResponse,
+    RateLimitConfig, Transport, TransportResponse,
+};
+#[cfg(test)]
+use bytes::Bytes;
+#[cfg(test)]
+use futures::future::BoxFuture;
+#[cfg(test)]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[tokio::test]
+async fn test_preflight_drops_unreachable_domains() {
+    // unserved urls answer 404, which still counts as reachable
+    let reachable = "http://up.example/".to_string();
+    let unreachable = "http://down.example/".to_string();
+    let cdn = FakeCdn::new();
+    cdn.insert(unreachable.clone(), FakeResponse::ConnectionFailed);
+    let client = HttpClient::with_transport(cdn, &RateLimitConfig::default());
+    let preflight = preflight_domains(&[unreachable.clone(), reachable.clone()], &client)
+        .await
+        .unwrap();
+    assert_eq!(preflight.reachable, vec![reachable]);
+    assert_eq!(preflight.unreachable.len(), 1);
+    assert_eq!(preflight.unreachable[0].0, unreachable);
+}
+
+/// Fails with a connection error on the first request and answers every later one.
+#[cfg(test)]
+#[derive(Default)]
+struct FailsOnce {
+    requests: AtomicUsize,
+}
+
+#[cfg(test)]
+impl Transport for FailsOnce {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        let count = self.requests.fetch_add(1, Ordering::Relaxed);
+        Box::pin(async move {
+            if count == 0 {
+                return Err(ConnectionFailed(url.to_string()).into());
+            }
+            Ok(TransportResponse::new(
+                url,
+                200,
+                Default::default(),
+                Bytes::new(),
+            ))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_preflight_retries_connection_errors_once() {
+    let transport = Arc::new(FailsOnce::default());
+    let client = HttpClient::with_transport(transport.clone(), &RateLimitConfig::default());
+    let preflight = preflight_domains(&["http://flaky.example/"], &client)
+        .await
+        .unwrap();
+    assert_eq!(preflight.reachable, vec!["http://flaky.example/"]);
+    assert_eq!(transport.requests.load(Ordering::Relaxed), 2);
+
+    let cdn = FakeCdn::new();
+    cdn.insert("http://down.example/", FakeResponse::ConnectionFailed);
+    let client = HttpClient::with_transport(cdn.clone(), &RateLimitConfig::default());
+    let preflight = preflight_domains(&["http://down.example/"], &client)
+        .await
+        .unwrap();
+    assert!(preflight.reachable.is_empty());
+    assert_eq!(cdn.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_preflight_fails_when_cancelled_or_out_of_budget() {
+    let cdn = FakeCdn::new();
+    let client = HttpClient::with_transport(cdn.clone(), &RateLimitConfig::default());
+    let token = CancellationToken::new();
+    client.set_cancellation_token(token.clone());
+    token.cancel();
+    let err = preflight_domains(&["http://a.example/"], &client)
+        .await
+        .err()
+        .unwrap();
+    assert!(err.is::<Cancelled>());
+
+    let client = HttpClient::with_transport(
+        cdn.clone(),
+        &RateLimitConfig {
+            max_requests: Some(1),
+            ..RateLimitConfig::default()
+        },
+    );
+    let err = preflight_domains(&["http://a.example/", "http://b.example/"], &client)
+        .await
+        .err()
+        .unwrap();
+    assert!(err.is::<BudgetExhausted>());
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use anyhow::{anyhow, Context};
+use url::Url;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyRotation {
+    /// Use the next proxy in the pool for every request.
+    #[default]
+    PerRequest,
+    /// Always use the same proxy for the same host.
+    PerDomain,
+}
+
+/// A pool of HTTP or SOCKS5 proxies, e.g. `http://10.0.0.1:3128` or `socks5://127.0.0.1:1080`.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+    pub proxies: Vec<String>,
+    pub rotation: ProxyRotation,
+    /// Hosts that must always go through a specific proxy, e.g. `("vod-secure.twitch.tv", "socks5://127.0.0.1:1080")`.
+    pub assignments: Vec<(String, String)>,
+}
+
+/// A `ProxyConfig` with the list of proxies and the hosts of the assignments worked out once,
+/// so that picking the proxy of a request doesn't have to.
+#[derive(Clone, Debug, Default)]
+pub struct ProxySelector {
+    all: Vec<String>,
+    pool_size: usize,
+    rotation: ProxyRotation,
+    /// The host of every assignment with the index of its proxy in `all`.
+    assigned_hosts: Vec<(String, usize)>,
+}
+
+impl ProxySelector {
+    pub fn new(config: &ProxyConfig) -> Self {
+        let mut all = config.proxies.clone();
+        let mut assigned_hosts = vec![];
+        for (domain, proxy) in &config.assignments {
+            let index = match all.iter().position(|p| p == proxy) {
+                Some(index) => index,
+                None => {
+                    all.push(proxy.clone());
+                    all.len() - 1
+                }
+            };
+            let host = url_host(domain).unwrap_or_else(|| domain.clone());
+            assigned_hosts.push((host, index));
+        }
+        Self {
+            all,
+            pool_size: config.proxies.len(),
+            rotation: config.rotation,
+            assigned_hosts,
+        }
+    }
+
+    /// Every distinct proxy that a client needs to be built for, in a fixed order:
+    /// the pool first, then the proxies that are only used for assigned domains.
+    pub fn all_proxies(&self) -> &[String] {
+        &self.all
+    }
+
+    /// Whether requests without an assigned proxy are sent directly.
+    pub fn has_pool(&self) -> bool {
+        self.pool_size > 0
+    }
+
+    /// Picks the index into `all_proxies` that a request to `url` should use.
+    /// `counter` is the number of requests made so far and drives per-request rotation.
+    pub fn select(&self, url: &str, counter: usize) -> Option<usize> {
+        if self.all.is_empty() {
+            return None;
+        }
+        let host = url_host(url);
+        if let Some(host) = &host {
+            let assigned = self
+                .assigned_hosts
+                .iter()
+                .find(|(domain, _)| domain == host);
+            if let Some((_, index)) = assigned {
+                return Some(*index);
+            }
+        }
+        if self.pool_size == 0 {
+            return None;
+        }
+        let index = match (self.rotation, host) {
+            (ProxyRotation::PerDomain, Some(host)) => {
+                let mut hasher = DefaultHasher::new();
+                host.hash(&mut hasher);
+                hasher.finish() as usize
+            }
+            _ => counter,
+        };
+        Some(index % self.pool_size)
+    }
+
+    /// Like `select`, but only if the answer doesn't depend on the order of requests.
+    pub fn select_fixed(&self, url: &str) -> Option<usize> {
+        let host = url_host(url)?;
+        let assigned = self
+            .assigned_hosts
+            .iter()
+            .any(|(domain, _)| *domain == host);
+        if assigned || self.rotation == ProxyRotation::PerDomain {
+            self.select(url, 0)
+        } else {
+            None
+        }
+    }
+}
+
+fn url_host(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(str::to_string))
+}
+
+/// Parses `domain=proxy`, where `domain` is a host or a url like `https://vod-secure.twitch.tv/`.
+pub fn parse_proxy_assignment(assignment: &str) -> anyhow::Result<(String, String)> {
+    let (domain, proxy) = assignment
+        .split_once('=')
+        .context("proxy assignment must look like domain=proxy")?;
+    if domain.is_empty() || proxy.is_empty() {
+        return Err(anyhow!("proxy assignment must look like domain=proxy"));
+    }
+    Ok((domain.to_string(), proxy.to_string()))
+}
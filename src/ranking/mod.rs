@@ -0,0 +1,151 @@
+mod tests;
+
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::HostObservation;
+
+/// Twitch keeps VODs for 7, 14 or 60 days, so domains are compared within these age ranges.
+pub const AGE_BUCKETS_DAYS: [i64; 3] = [7, 14, 60];
+
+/// Weight of the newest run in the moving averages.
+const SMOOTHING: f64 = 0.3;
+
+/// How often each domain held a VOD and how healthy it was, across past runs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DomainStats {
+    pub domains: BTreeMap<String, DomainRecord>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DomainRecord {
+    /// Indexed like `AGE_BUCKETS_DAYS`, plus one bucket for older VODs.
+    pub buckets: Vec<HitCounts>,
+    pub average_latency_milliseconds: Option<f64>,
+    /// Connection failures, halved after every run so that old failures are forgotten.
+    pub recent_failures: f64,
+    /// Unix time of the last search that this domain won.
+    pub last_hit: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct HitCounts {
+    pub searches: u32,
+    pub hits: u32,
+}
+
+/// Lets the first `leaders` domains probe alone for `delay` before the rest start.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeadStart {
+    pub leaders: usize,
+    pub delay: Duration,
+}
+
+impl HeadStart {
+    pub fn delay_for(&self, rank: usize) -> Duration {
+        if rank < self.leaders {
+            Duration::ZERO
+        } else {
+            self.delay
+        }
+    }
+}
+
+pub fn age_bucket(vod_age: chrono::Duration) -> usize {
+    let days = vod_age.num_days();
+    AGE_BUCKETS_DAYS
+        .iter()
+        .position(|limit| days < *limit)
+        .unwrap_or(AGE_BUCKETS_DAYS.len())
+}
+
+impl DomainRecord {
+    fn bucket_mut(&mut self, bucket: usize) -> &mut HitCounts {
+        if self.buckets.len() <= bucket {
+            self.buckets
+                .resize(AGE_BUCKETS_DAYS.len() + 1, HitCounts::default());
+        }
+        &mut self.buckets[bucket]
+    }
+
+    /// Estimated chance that this domain has a VOD of the given age.
+    /// Domains without history start at one half.
+    pub fn hit_rate(&self, bucket: usize) -> f64 {
+        let counts = self.buckets.get(bucket).copied().unwrap_or_default();
+        (counts.hits as f64 + 1.0) / (counts.searches as f64 + 2.0)
+    }
+
+    pub fn score(&self, bucket: usize) -> f64 {
+        self.hit_rate(bucket) / (1.0 + self.recent_failures)
+    }
+}
+
+impl DomainStats {
+    /// Records which of the `probed` domains held the VOD.
+    pub fn record_search<T: Display>(
+        &mut self,
+        probed: &[T],
+        winner: Option<&T>,
+        vod_age: chrono::Duration,
+        now_unix: i64,
+    ) {
+        let bucket = age_bucket(vod_age);
+        let winner = winner.map(|winner| winner.to_string());
+        for domain in probed {
+            let domain = domain.to_string();
+            let is_winner = winner.as_ref() == Some(&domain);
+            let record = self.domains.entry(domain).or_default();
+            let counts = record.bucket_mut(bucket);
+            counts.searches += 1;
+            if is_winner {
+                counts.hits += 1;
+                record.last_hit = Some(now_unix);
+            }
+        }
+    }
+
+    /// Folds the latencies and connection failures that the client saw during one run into the stats.
+    pub fn record_observations<'a>(
+        &mut self,
+        observations: impl IntoIterator<Item = (&'a String, &'a HostObservation)>,
+    ) {
+        for record in self.domains.values_mut() {
+            record.recent_failures /= 2.0;
+        }
+        for (domain, observation) in observations {
+            let record = self.domains.entry(String::clone(domain)).or_default();
+            record.recent_failures += observation.connection_failures as f64;
+            if let Some(latency) = observation.average_latency() {
+                let latency = latency.as_secs_f64() * 1000.0;
+                record.average_latency_milliseconds =
+                    Some(match record.average_latency_milliseconds {
+                        Some(average) => average * (1.0 - SMOOTHING) + latency * SMOOTHING,
+                        None => latency,
+                    });
+            }
+        }
+    }
+
+    /// Orders `domains` from most to least promising for a VOD of the given age.
+    /// Ties are broken by latency, then by the original order.
+    pub fn rank<T: Display + Clone>(&self, domains: &[T], vod_age: chrono::Duration) -> Vec<T> {
+        let bucket = age_bucket(vod_age);
+        let mut scored = domains
+            .iter()
+            .map(|domain| {
+                let record = self.domains.get(&domain.to_string());
+                let score = record.map(|r| r.score(bucket)).unwrap_or(0.5);
+                let latency = record
+                    .and_then(|r| r.average_latency_milliseconds)
+                    .unwrap_or(f64::MAX);
+                (score, latency, domain)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.total_cmp(&b.1)));
+        scored
+            .into_iter()
+            .map(|(_, _, domain)| domain.clone())
+            .collect()
+    }
+}
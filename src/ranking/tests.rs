@@ -0,0 +1,54 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_age_bucket() {
+    assert_eq!(age_bucket(chrono::Duration::days(1)), 0);
+    assert_eq!(age_bucket(chrono::Duration::days(10)), 1);
+    assert_eq!(age_bucket(chrono::Duration::days(30)), 2);
+    assert_eq!(age_bucket(chrono::Duration::days(365)), 3);
+}
+
+#[test]
+fn test_rank_prefers_domains_with_hits() {
+    let domains = ["https://a/", "https://b/", "https://c/"];
+    let mut stats = DomainStats::default();
+    let age = chrono::Duration::days(2);
+    for _ in 0..3 {
+        stats.record_search(&domains, Some(&"https://c/"), age, 0);
+    }
+    stats.record_search(&domains, Some(&"https://b/"), age, 0);
+    assert_eq!(
+        stats.rank(&domains, age),
+        vec!["https://c/", "https://b/", "https://a/"]
+    );
+    // the hits were for young VODs, so old VODs keep the original order
+    assert_eq!(stats.rank(&domains, chrono::Duration::days(100)), domains);
+}
+
+#[test]
+fn test_rank_penalizes_failures() {
+    let domains = ["https://a/".to_string(), "https://b/".to_string()];
+    let mut stats = DomainStats::default();
+    let observation = HostObservation {
+        requests: 10,
+        responses: 0,
+        connection_failures: 10,
+        total_latency: Duration::ZERO,
+    };
+    stats.record_observations([(&domains[0], &observation)]);
+    assert_eq!(
+        stats.rank(&domains, chrono::Duration::days(1)),
+        vec![domains[1].clone(), domains[0].clone()]
+    );
+}
+
+#[test]
+fn test_head_start() {
+    let head_start = HeadStart {
+        leaders: 2,
+        delay: Duration::from_millis(100),
+    };
+    assert_eq!(head_start.delay_for(1), Duration::ZERO);
+    assert_eq!(head_start.delay_for(2), Duration::from_millis(100));
+}
@@ -0,0 +1,71 @@
+mod tests;
+
+use rand::Rng;
+
+/// z-score of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Splits `0..len` into `count` equally sized strata and picks one random index in each, so that
+/// the sample covers the whole VOD. Every index is returned if `count` is at least `len`.
+pub fn stratified_sample_indices<R: Rng>(len: usize, count: usize, rng: &mut R) -> Vec<usize> {
+    if count >= len {
+        return (0..len).collect();
+    }
+    (0..count)
+        .map(|i| {
+            let start = i * len / count;
+            let end = (i + 1) * len / count;
+            rng.gen_range(start..end)
+        })
+        .collect()
+}
+
+/// Share of valid segments in a VOD, estimated from a sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AvailabilityEstimate {
+    pub checked: usize,
+    pub valid: usize,
+    /// Number of segments in the VOD.
+    pub population: usize,
+    pub estimate: f64,
+    /// Bounds of the 95% confidence interval.
+    pub low: f64,
+    pub high: f64,
+}
+
+impl AvailabilityEstimate {
+    /// Uses the Wilson score interval, which stays sensible when every sampled segment is valid
+    /// or every one is dead. A sample of the whole VOD is exact.
+    pub fn new(valid: usize, checked: usize, population: usize) -> Self {
+        let estimate = if checked == 0 {
+            0.0
+        } else {
+            valid as f64 / checked as f64
+        };
+        let (low, high) = if checked == 0 {
+            (0.0, 1.0)
+        } else if checked >= population {
+            (estimate, estimate)
+        } else {
+            let n = checked as f64;
+            let z2 = Z_95 * Z_95;
+            let center = (estimate + z2 / (2.0 * n)) / (1.0 + z2 / n);
+            let margin = Z_95 / (1.0 + z2 / n)
+                * (estimate * (1.0 - estimate) / n + z2 / (4.0 * n * n)).sqrt();
+            ((center - margin).max(0.0), (center + margin).min(1.0))
+        };
+        Self {
+            checked,
+            valid,
+            population,
+            estimate,
+            low,
+            high,
+        }
+    }
+
+    /// Some sampled segments are valid and some are dead, so only a full check can tell which.
+    pub fn is_partial_loss(&self) -> bool {
+        self.valid > 0 && self.valid < self.checked
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn test_stratified_sample_indices() {
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(stratified_sample_indices(3, 5, &mut rng), vec![0, 1, 2]);
+    let indices = stratified_sample_indices(100, 10, &mut rng);
+    assert_eq!(indices.len(), 10);
+    for (i, index) in indices.into_iter().enumerate() {
+        assert!((i * 10..(i + 1) * 10).contains(&index));
+    }
+}
+
+#[test]
+fn test_availability_estimate() {
+    let all_valid = AvailabilityEstimate::new(20, 20, 1000);
+    assert_eq!(all_valid.estimate, 1.0);
+    assert_eq!(all_valid.high, 1.0);
+    assert!(all_valid.low > 0.8 && all_valid.low < 0.9);
+    assert!(!all_valid.is_partial_loss());
+
+    let half = AvailabilityEstimate::new(50, 100, 1000);
+    assert!(half.low < 0.5 && half.high > 0.5);
+    assert!((half.high - 0.5 - (0.5 - half.low)).abs() < 1e-9);
+    assert!(half.is_partial_loss());
+
+    let exact = AvailabilityEstimate::new(7, 10, 10);
+    assert_eq!((exact.low, exact.high), (0.7, 0.7));
+}
@@ -0,0 +1,124 @@
+mod tests;
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, NaiveDateTime, NaiveTime};
+use m3u8_rs::MediaPlaylist;
+
+use crate::TransformContext;
+
+/// Where a moment of the stream is in a VOD's playlist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VodPosition {
+    /// How long after the start of the stream the moment is.
+    pub vod_offset: Duration,
+    /// The index into the playlist of the segment that contains the moment.
+    /// If the moment was removed from the playlist, this is the next segment that is left.
+    pub segment_index: usize,
+    pub segment_uri: String,
+    /// How far into the segment the moment is.
+    pub segment_offset: Duration,
+    /// Where a player that plays the playlist has to seek to.
+    pub playlist_offset: Duration,
+    /// Set if the moment was removed from the playlist, to how long before the next segment
+    /// it is.
+    pub gap: Option<Duration>,
+}
+
+impl VodPosition {
+    /// The VOD on twitch.tv at the moment, e.g. `https://www.twitch.tv/videos/123?t=1h2m3s`.
+    pub fn twitch_url(&self, video_id: &str) -> String {
+        let seconds = self.vod_offset.as_secs();
+        format!(
+            "https://www.twitch.tv/videos/{}?t={}h{}m{}s",
+            video_id,
+            seconds / (60 * 60),
+            (seconds / 60) % 60,
+            seconds % 60
+        )
+    }
+}
+
+/// Finds the segment of `playlist` that was streamed at the UTC `time`.
+///
+/// The offset of each segment comes from its `EXT-X-PROGRAM-DATE-TIME` tag if it has one,
+/// then from `context.offsets`, and otherwise it is assumed to follow the segment before it.
+/// So segments that were removed from `playlist` are accounted for if the tags were written or
+/// the context was built from the VOD's full playlist.
+pub fn locate_time(
+    playlist: &MediaPlaylist,
+    context: &TransformContext,
+    time: NaiveDateTime,
+) -> anyhow::Result<VodPosition> {
+    let start_time = context
+        .start_time
+        .context("the start time of the stream is unknown")?;
+    let vod_offset = (time - start_time)
+        .to_std()
+        .map_err(|_| anyhow!("{} is before the stream started at {}", time, start_time))?;
+    let mut next_offset = Duration::ZERO;
+    let mut playlist_offset = Duration::ZERO;
+    for (index, segment) in playlist.segments.iter().enumerate() {
+        let duration = Duration::from_secs_f64(segment.duration as f64);
+        let offset = match segment.program_date_time {
+            Some(date_time) => (date_time.naive_utc() - start_time).to_std().map_err(|_| {
+                anyhow!(
+                    "segment {} is stamped at {}, before the stream started at {}",
+                    segment.uri,
+                    date_time.naive_utc(),
+                    start_time
+                )
+            })?,
+            None => context.offset(&segment.uri).unwrap_or(next_offset),
+        };
+        next_offset = offset + duration;
+        if vod_offset < next_offset {
+            let gap = offset.checked_sub(vod_offset).filter(|gap| !gap.is_zero());
+            let segment_offset = vod_offset.saturating_sub(offset);
+            return Ok(VodPosition {
+                vod_offset,
+                segment_index: index,
+                segment_uri: segment.uri.clone(),
+                segment_offset,
+                playlist_offset: playlist_offset + segment_offset,
+                gap,
+            });
+        }
+        playlist_offset += duration;
+    }
+    Err(anyhow!(
+        "{} is after the end of the VOD at {}",
+        time,
+        start_time + chrono::Duration::from_std(next_offset)?
+    ))
+}
+
+/// Parses a UTC time like '2022-09-24 18:14:00', '2022-09-24T18:14:00Z' or just '18:14'.
+/// A time without a date is on the day the stream started, or on the next day if that is more
+/// than an hour before the start, for streams that went past midnight.
+pub fn parse_utc_time(time: &str, start_time: NaiveDateTime) -> anyhow::Result<NaiveDateTime> {
+    let time = time.trim();
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(date_time.naive_utc());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(time, format) {
+            return Ok(date_time);
+        }
+    }
+    let time_of_day = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .with_context(|| {
+            format!(
+                "invalid time '{}', expected e.g. '2022-09-24 18:14:00' or '18:14'",
+                time
+            )
+        })?;
+    let date_time = start_time.date().and_time(time_of_day);
+    if start_time - date_time > chrono::Duration::hours(1) {
+        Ok(date_time + chrono::Duration::days(1))
+    } else {
+        Ok(date_time)
+    }
+}
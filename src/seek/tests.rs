@@ -0,0 +1,93 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::testing::{context, playlist, start_time};
+#[cfg(test)]
+use std::collections::HashMap;
+
+#[test]
+fn test_locate_time_in_full_playlist() {
+    let playlist = playlist(&["0.ts", "1.ts", "2.ts"]);
+    let time = parse_utc_time("2022-09-24 17:02:34", start_time()).unwrap();
+    let position = locate_time(&playlist, &context(HashMap::new()), time).unwrap();
+    assert_eq!(
+        position,
+        VodPosition {
+            vod_offset: Duration::from_secs(25),
+            segment_index: 2,
+            segment_uri: "2.ts".to_string(),
+            segment_offset: Duration::from_secs(5),
+            playlist_offset: Duration::from_secs(25),
+            gap: None,
+        }
+    );
+    assert_eq!(
+        position.twitch_url("123"),
+        "https://www.twitch.tv/videos/123?t=0h0m25s"
+    );
+    let end = parse_utc_time("17:02:39", start_time()).unwrap();
+    assert!(locate_time(&playlist, &context(HashMap::new()), end).is_err());
+    let before = parse_utc_time("17:02", start_time()).unwrap();
+    assert!(locate_time(&playlist, &context(HashMap::new()), before).is_err());
+}
+
+#[test]
+fn test_locate_time_after_removed_segments() {
+    let filtered = playlist(&["0.ts", "3.ts", "4.ts"]);
+    let offsets = (0..5)
+        .map(|n| (n, Duration::from_secs(10 * n as u64)))
+        .collect();
+    let context = context(offsets);
+    let time = parse_utc_time("17:02:51", start_time()).unwrap();
+    let position = locate_time(&filtered, &context, time).unwrap();
+    assert_eq!(position.segment_uri, "4.ts");
+    assert_eq!(position.segment_offset, Duration::from_secs(2));
+    assert_eq!(position.playlist_offset, Duration::from_secs(22));
+    assert_eq!(position.gap, None);
+
+    let removed = parse_utc_time("17:02:24", start_time()).unwrap();
+    let position = locate_time(&filtered, &context, removed).unwrap();
+    assert_eq!(position.segment_uri, "3.ts");
+    assert_eq!(position.playlist_offset, Duration::from_secs(10));
+    assert_eq!(position.gap, Some(Duration::from_secs(15)));
+}
+
+#[test]
+fn test_locate_time_from_program_date_time() {
+    let mut filtered = playlist(&["0.ts", "3.ts"]);
+    let start = chrono::TimeZone::from_utc_datetime(&chrono::Utc, &start_time()).fixed_offset();
+    filtered.segments[0].program_date_time = Some(start);
+    filtered.segments[1].program_date_time = Some(start + chrono::Duration::seconds(30));
+    let time = parse_utc_time("2022-09-24T17:02:42Z", start_time()).unwrap();
+    let position = locate_time(&filtered, &context(HashMap::new()), time).unwrap();
+    assert_eq!(position.segment_index, 1);
+    assert_eq!(position.vod_offset, Duration::from_secs(33));
+    assert_eq!(position.playlist_offset, Duration::from_secs(13));
+}
+
+#[test]
+fn test_locate_time_rejects_segments_stamped_before_the_start() {
+    let mut stamped = playlist(&["0.ts", "1.ts"]);
+    let start = chrono::TimeZone::from_utc_datetime(&chrono::Utc, &start_time()).fixed_offset();
+    stamped.segments[0].program_date_time = Some(start - chrono::Duration::seconds(10));
+    stamped.segments[1].program_date_time = Some(start);
+    let time = parse_utc_time("2022-09-24T17:02:15Z", start_time()).unwrap();
+    let err = locate_time(&stamped, &context(HashMap::new()), time).unwrap_err();
+    assert!(
+        err.to_string().contains("before the stream started"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_parse_utc_time() {
+    let start = NaiveDateTime::parse_from_str("2022-09-24 22:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let parse = |time| parse_utc_time(time, start).unwrap().to_string();
+    assert_eq!(parse("23:14"), "2022-09-24 23:14:00");
+    assert_eq!(parse("01:02:03"), "2022-09-25 01:02:03");
+    assert_eq!(parse("21:30"), "2022-09-24 21:30:00");
+    assert_eq!(parse("2022-09-25 01:02"), "2022-09-25 01:02:00");
+    assert_eq!(parse("2022-09-25T01:02:03+02:00"), "2022-09-24 23:02:03");
+    assert!(parse_utc_time("yesterday", start).is_err());
+}
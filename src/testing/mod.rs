@@ -0,0 +1,96 @@
+//! Fixtures shared by the tests of the other modules.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::NaiveDateTime;
+use futures::future::BoxFuture;
+use m3u8_rs::{MediaPlaylist, MediaSegment};
+use reqwest::StatusCode;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use crate::{TransformContext, Transport, TransportResponse};
+
+/// When the stream of the fixtures started.
+pub fn start_time() -> NaiveDateTime {
+    NaiveDateTime::parse_from_str("2022-09-24 17:02:09", "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+/// A playlist of 10 second segments with the given uris.
+pub fn playlist(uris: &[&str]) -> MediaPlaylist {
+    MediaPlaylist {
+        segments: uris
+            .iter()
+            .map(|uri| MediaSegment {
+                uri: uri.to_string(),
+                duration: 10.0,
+                ..MediaSegment::default()
+            })
+            .collect(),
+        ..MediaPlaylist::default()
+    }
+}
+
+/// The context of a stream that started at `start_time`, with the offsets of its segments by
+/// sequence number.
+pub fn context(offsets: HashMap<usize, Duration>) -> TransformContext {
+    TransformContext {
+        start_time: Some(start_time()),
+        offsets,
+        ..TransformContext::default()
+    }
+}
+
+/// Never answers, like a server that accepted the connection and then hung.
+pub struct HangingTransport;
+
+impl Transport for HangingTransport {
+    fn get<'a>(&'a self, _: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+/// Serves HTTP/1.1 on a local port for the rest of the test and returns its base url.
+/// Every request is answered with the status and body that `respond` returns for its request
+/// line, e.g. `GET http://cdn.example/ HTTP/1.1` when the server is used as a proxy.
+/// Tests that don't need a real socket should use `FakeCdn` instead.
+pub async fn serve_http<F>(respond: F) -> String
+where
+    F: Fn(&str) -> (u16, String) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let respond = Arc::new(respond);
+    tokio::task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let respond = Arc::clone(&respond);
+            tokio::task::spawn(async move {
+                let mut buffer = vec![0; 4096];
+                let mut read = 0;
+                while !buffer[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buffer[read..]).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => read += n,
+                    }
+                }
+                let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                let (status, body) = respond(request.lines().next().unwrap_or_default());
+                let reason = StatusCode::from_u16(status)
+                    .ok()
+                    .and_then(|status| status.canonical_reason())
+                    .unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    reason,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    url
+}
@@ -0,0 +1,437 @@
+mod tests;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use m3u8_rs::MediaPlaylist;
+
+use crate::{mute_uri, DomainWithPath};
+
+/// One step of post-processing a playlist before it is written.
+pub trait PlaylistTransform: Send + Sync {
+    /// The name used in transform lists, e.g. 'mute'.
+    fn name(&self) -> String;
+
+    fn apply(&self, playlist: &mut MediaPlaylist, context: &TransformContext)
+        -> anyhow::Result<()>;
+}
+
+/// What the transforms know about the VOD besides the playlist they are given.
+///
+/// Segments are identified by the sequence number in their file name, e.g. 123 for
+/// `123-muted.ts`, so that transforms can tell where segments were removed.
+#[derive(Clone, Debug, Default)]
+pub struct TransformContext {
+    /// The directory of the segments, e.g. `https://vod-secure.twitch.tv/{url_path}/chunked/`.
+    pub base_url: Option<String>,
+    /// The UTC start time of the stream.
+    pub start_time: Option<NaiveDateTime>,
+    /// The offset of every segment from the start of the VOD, by sequence number.
+    pub offsets: HashMap<usize, Duration>,
+    /// The sequence numbers of the segments that were checked and found valid.
+    /// `None` if the segments weren't checked.
+    pub valid: Option<HashSet<usize>>,
+    /// The sequence numbers of the muted segments whose unmuted versions were checked and
+    /// found valid. `None` if they weren't checked.
+    pub unmuted: Option<HashSet<usize>>,
+}
+
+impl TransformContext {
+    /// Takes the offsets from `playlist`, which should be the VOD's playlist before any
+    /// segments were removed.
+    pub fn new<T: Clone + 'static + Send + Display>(
+        playlist: &MediaPlaylist,
+        dwp: &DomainWithPath<T>,
+    ) -> Self {
+        let mut offsets = HashMap::new();
+        let mut offset = Duration::ZERO;
+        for (index, segment) in playlist.segments.iter().enumerate() {
+            let number = segment_sequence_number(&segment.uri).unwrap_or(index);
+            offsets.insert(number, offset);
+            offset += Duration::from_secs_f64(segment.duration as f64);
+        }
+        Self {
+            base_url: Some(format!("{}{}/chunked/", dwp.domain, dwp.path.url_path)),
+            start_time: Some(dwp.path.video_data.unix_time_seconds),
+            offsets,
+            valid: None,
+            unmuted: None,
+        }
+    }
+
+    /// Marks the segments of `playlist` as the valid ones, e.g. after filtering a copy of the
+    /// playlist.
+    pub fn with_valid_segments(mut self, playlist: &MediaPlaylist) -> Self {
+        self.valid = Some(
+            playlist
+                .segments
+                .iter()
+                .filter_map(|segment| segment_sequence_number(&segment.uri))
+                .collect(),
+        );
+        self
+    }
+
+    /// Marks the unmuted versions of the segments at `uris` as the valid ones.
+    pub fn with_unmuted_segments<'a>(mut self, uris: impl IntoIterator<Item = &'a str>) -> Self {
+        self.unmuted = Some(
+            uris.into_iter()
+                .filter_map(segment_sequence_number)
+                .collect(),
+        );
+        self
+    }
+
+    /// The offset of the segment at `uri` from the start of the VOD, if it is known.
+    pub fn offset(&self, uri: &str) -> Option<Duration> {
+        self.offsets.get(&segment_sequence_number(uri)?).copied()
+    }
+}
+
+/// The number at the start of the file name of a segment, e.g. 123 for
+/// `https://vod-secure.twitch.tv/{url_path}/chunked/123-muted.ts`.
+pub fn segment_sequence_number(uri: &str) -> Option<usize> {
+    let file_name = uri.rsplit('/').next().unwrap_or(uri);
+    let digits = file_name
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(file_name, |end| &file_name[..end]);
+    digits.parse().ok()
+}
+
+/// Replaces the segments whose audio was muted with their muted versions,
+/// since the unmuted versions are usually gone.
+pub struct Mute;
+
+/// Uses the unmuted versions of muted segments where they were checked and found valid, and
+/// the muted versions everywhere else.
+pub struct Unmute;
+
+/// Turns the segment file names into full urls so that the playlist works from anywhere.
+pub struct ExplicitPaths;
+
+/// Turns the segment urls back into bare file names, e.g. for a playlist that is served from a
+/// copy of the `chunked` directory.
+pub struct RelativePaths;
+
+/// Drops the segments that were checked and found invalid.
+pub struct Filter;
+
+/// Keeps the segments that start between `start` and `end` into the VOD.
+pub struct Trim {
+    pub start: Duration,
+    pub end: Option<Duration>,
+}
+
+/// Adds `EXT-X-DISCONTINUITY` before every segment that doesn't follow the one before it,
+/// so that players don't expect the timestamps to be continuous.
+pub struct MarkDiscontinuities;
+
+/// Stamps every segment with `EXT-X-PROGRAM-DATE-TIME`, computed from the start time of the
+/// stream and the segment's offset into the VOD.
+pub struct ProgramDateTime;
+
+impl PlaylistTransform for Mute {
+    fn name(&self) -> String {
+        "mute".to_string()
+    }
+
+    fn apply(&self, playlist: &mut MediaPlaylist, _: &TransformContext) -> anyhow::Result<()> {
+        for segment in &mut playlist.segments {
+            mute_uri(&mut segment.uri);
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for Unmute {
+    fn name(&self) -> String {
+        "unmute".to_string()
+    }
+
+    fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        let unmuted = context.unmuted.clone().unwrap_or_default();
+        for segment in &mut playlist.segments {
+            let is_unmuted = segment_sequence_number(&segment.uri)
+                .is_some_and(|number| unmuted.contains(&number));
+            if is_unmuted {
+                if let Some(start) = segment.uri.find("-muted.ts") {
+                    segment.uri = String::from(&segment.uri[..start]) + "-unmuted.ts";
+                }
+            } else {
+                mute_uri(&mut segment.uri);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for ExplicitPaths {
+    fn name(&self) -> String {
+        "explicit".to_string()
+    }
+
+    fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        let base_url = context
+            .base_url
+            .as_ref()
+            .context("explicit paths need the url of the VOD")?;
+        for segment in &mut playlist.segments {
+            if !segment.uri.contains("://") {
+                segment.uri = format!("{}{}", base_url, segment.uri);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for RelativePaths {
+    fn name(&self) -> String {
+        "relative".to_string()
+    }
+
+    fn apply(&self, playlist: &mut MediaPlaylist, _: &TransformContext) -> anyhow::Result<()> {
+        for segment in &mut playlist.segments {
+            if let Some((_, file_name)) = segment.uri.rsplit_once('/') {
+                segment.uri = file_name.to_string();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for Filter {
+    fn name(&self) -> String {
+        "filter".to_string()
+    }
+
+    /// Does nothing if the segments weren't checked.
+    fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        if let Some(valid) = &context.valid {
+            playlist.segments.retain(|segment| {
+                segment_sequence_number(&segment.uri).is_none_or(|n| valid.contains(&n))
+            });
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for Trim {
+    fn name(&self) -> String {
+        let end = self.end.map(|end| end.as_secs().to_string());
+        format!("trim={}-{}", self.start.as_secs(), end.unwrap_or_default())
+    }
+
+    fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        let mut offset = Duration::ZERO;
+        playlist.segments.retain(|segment| {
+            let start = context.offset(&segment.uri).unwrap_or(offset);
+            offset = start + Duration::from_secs_f64(segment.duration as f64);
+            start >= self.start && self.end.is_none_or(|end| start < end)
+        });
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for MarkDiscontinuities {
+    fn name(&self) -> String {
+        "discontinuities".to_string()
+    }
+
+    fn apply(&self, playlist: &mut MediaPlaylist, _: &TransformContext) -> anyhow::Result<()> {
+        let mut previous = None;
+        for segment in &mut playlist.segments {
+            let number = segment_sequence_number(&segment.uri);
+            if let (Some(previous), Some(number)) = (previous, number) {
+                if number != previous + 1 {
+                    segment.discontinuity = true;
+                }
+            }
+            previous = number;
+        }
+        Ok(())
+    }
+}
+
+impl PlaylistTransform for ProgramDateTime {
+    fn name(&self) -> String {
+        "program-date-time".to_string()
+    }
+
+    fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        let start_time = context
+            .start_time
+            .context("program date-times need the start time of the stream")?;
+        let start_time = Utc.from_utc_datetime(&start_time).fixed_offset();
+        let mut offset = Duration::ZERO;
+        for segment in &mut playlist.segments {
+            let start = context.offset(&segment.uri).unwrap_or(offset);
+            segment.program_date_time = Some(start_time + chrono::Duration::from_std(start)?);
+            offset = start + Duration::from_secs_f64(segment.duration as f64);
+        }
+        Ok(())
+    }
+}
+
+/// A list of transforms that are applied in order.
+/// Lists are written like 'mute,explicit,filter,trim=600-3600'.
+#[derive(Clone)]
+pub struct TransformPipeline {
+    transforms: Vec<Arc<dyn PlaylistTransform>>,
+}
+
+impl Default for TransformPipeline {
+    /// Mutes, makes the paths explicit and drops the invalid segments.
+    fn default() -> Self {
+        Self {
+            transforms: vec![Arc::new(Mute), Arc::new(ExplicitPaths), Arc::new(Filter)],
+        }
+    }
+}
+
+impl TransformPipeline {
+    pub fn new() -> Self {
+        Self { transforms: vec![] }
+    }
+
+    pub fn push(&mut self, transform: Arc<dyn PlaylistTransform>) {
+        self.transforms.push(transform);
+    }
+
+    /// Adds `discontinuities` and `program-date-time` to the end unless they are already there,
+    /// so that players know when every segment was streamed and where segments were removed.
+    pub fn with_program_date_time(mut self) -> Self {
+        if !self.contains("discontinuities") {
+            self.push(Arc::new(MarkDiscontinuities));
+        }
+        if !self.contains("program-date-time") {
+            self.push(Arc::new(ProgramDateTime));
+        }
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.transforms
+            .iter()
+            .any(|transform| transform.name().split('=').next() == Some(name))
+    }
+
+    pub fn apply(
+        &self,
+        playlist: &mut MediaPlaylist,
+        context: &TransformContext,
+    ) -> anyhow::Result<()> {
+        for transform in &self.transforms {
+            transform
+                .apply(playlist, context)
+                .with_context(|| format!("transform '{}' failed", transform.name()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TransformPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self
+            .transforms
+            .iter()
+            .map(|transform| transform.name())
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl FromStr for TransformPipeline {
+    type Err = anyhow::Error;
+
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        let mut pipeline = TransformPipeline::new();
+        for item in list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let (name, argument) = match item.split_once('=') {
+                Some((name, argument)) => (name, Some(argument)),
+                None => (item, None),
+            };
+            let transform: Arc<dyn PlaylistTransform> = match (name, argument) {
+                ("mute", None) => Arc::new(Mute),
+                ("unmute", None) => Arc::new(Unmute),
+                ("explicit", None) => Arc::new(ExplicitPaths),
+                ("relative", None) => Arc::new(RelativePaths),
+                ("filter", None) => Arc::new(Filter),
+                ("discontinuities", None) => Arc::new(MarkDiscontinuities),
+                ("program-date-time", None) => Arc::new(ProgramDateTime),
+                ("trim", Some(range)) => {
+                    let (start, end) = range
+                        .split_once('-')
+                        .context("trim takes a range like 'trim=10m-1h30m'")?;
+                    Arc::new(Trim {
+                        start: parse_offset(start)?,
+                        end: match end {
+                            "" => None,
+                            end => Some(parse_offset(end)?),
+                        },
+                    })
+                }
+                _ => return Err(anyhow!("unknown transform '{}'", item)),
+            };
+            pipeline.push(transform);
+        }
+        Ok(pipeline)
+    }
+}
+
+/// Parses an offset like '90', '90s', '10m' or '1h2m3s'. An empty string is zero.
+pub fn parse_offset(offset: &str) -> anyhow::Result<Duration> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in offset.trim().chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(anyhow!("invalid offset '{}'", offset)),
+        };
+        let value: u64 = number
+            .parse()
+            .with_context(|| format!("invalid offset '{}'", offset))?;
+        seconds += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        seconds += number.parse::<u64>()?;
+    }
+    Ok(Duration::from_secs(seconds))
+}
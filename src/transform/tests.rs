@@ -0,0 +1,228 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::{
+    testing::{playlist, start_time},
+    VideoData,
+};
+
+#[cfg(test)]
+fn uris(playlist: &MediaPlaylist) -> Vec<&str> {
+    playlist
+        .segments
+        .iter()
+        .map(|segment| segment.uri.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+fn context(original: &MediaPlaylist) -> TransformContext {
+    let mut offsets = HashMap::new();
+    for (index, segment) in original.segments.iter().enumerate() {
+        offsets.insert(
+            segment_sequence_number(&segment.uri).unwrap(),
+            Duration::from_secs(10 * index as u64),
+        );
+    }
+    TransformContext {
+        base_url: Some("https://cdn.example/path/chunked/".to_string()),
+        ..crate::testing::context(offsets)
+    }
+}
+
+#[test]
+fn test_segment_sequence_number() {
+    assert_eq!(segment_sequence_number("12.ts"), Some(12));
+    assert_eq!(segment_sequence_number("12-muted.ts"), Some(12));
+    assert_eq!(
+        segment_sequence_number("https://cdn.example/path/chunked/7-unmuted.ts"),
+        Some(7)
+    );
+    assert_eq!(segment_sequence_number("index-dvr.m3u8"), None);
+}
+
+#[test]
+fn test_default_pipeline_mutes_explicits_and_filters() {
+    let original = playlist(&["0.ts", "1-unmuted.ts", "2.ts"]);
+    let context = context(&original).with_valid_segments(&playlist(&["0.ts", "1-muted.ts"]));
+    let mut output = original.clone();
+    TransformPipeline::default()
+        .apply(&mut output, &context)
+        .unwrap();
+    assert_eq!(
+        uris(&output),
+        [
+            "https://cdn.example/path/chunked/0.ts",
+            "https://cdn.example/path/chunked/1-muted.ts"
+        ]
+    );
+}
+
+#[test]
+fn test_parse_pipeline() {
+    let pipeline: TransformPipeline = "unmute, relative,trim=1m-2h30m,program-date-time"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        pipeline.to_string(),
+        "unmute,relative,trim=60-9000,program-date-time"
+    );
+    assert!(pipeline.contains("trim"));
+    assert!(!pipeline.contains("filter"));
+    assert_eq!(
+        "mute,explicit,filter"
+            .parse::<TransformPipeline>()
+            .unwrap()
+            .to_string(),
+        TransformPipeline::default().to_string()
+    );
+    assert!("mute,shuffle".parse::<TransformPipeline>().is_err());
+    assert!("trim=10".parse::<TransformPipeline>().is_err());
+}
+
+#[test]
+fn test_trim_uses_the_original_timeline() {
+    let original = playlist(&["0.ts", "1.ts", "2.ts", "3.ts", "4.ts"]);
+    let context = context(&original);
+    let mut output = playlist(&["0.ts", "3.ts", "4.ts"]);
+    "trim=20-40"
+        .parse::<TransformPipeline>()
+        .unwrap()
+        .apply(&mut output, &context)
+        .unwrap();
+    assert_eq!(uris(&output), ["3.ts"]);
+}
+
+#[test]
+fn test_discontinuities_and_program_date_time() {
+    let original = playlist(&["0.ts", "1.ts", "2.ts", "3.ts"]);
+    let context = context(&original).with_valid_segments(&playlist(&["0.ts", "1.ts", "3.ts"]));
+    let mut output = original.clone();
+    "filter,discontinuities,program-date-time"
+        .parse::<TransformPipeline>()
+        .unwrap()
+        .apply(&mut output, &context)
+        .unwrap();
+    let discontinuities = output
+        .segments
+        .iter()
+        .map(|segment| segment.discontinuity)
+        .collect::<Vec<_>>();
+    assert_eq!(discontinuities, [false, false, true]);
+    let times = output
+        .segments
+        .iter()
+        .map(|segment| segment.program_date_time.unwrap().to_rfc3339())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [
+            "2022-09-24T17:02:09+00:00",
+            "2022-09-24T17:02:19+00:00",
+            "2022-09-24T17:02:39+00:00"
+        ]
+    );
+}
+
+#[test]
+fn test_custom_transform() {
+    struct Reverse;
+    impl PlaylistTransform for Reverse {
+        fn name(&self) -> String {
+            "reverse".to_string()
+        }
+
+        fn apply(&self, playlist: &mut MediaPlaylist, _: &TransformContext) -> anyhow::Result<()> {
+            playlist.segments.reverse();
+            Ok(())
+        }
+    }
+    let mut pipeline: TransformPipeline = "relative".parse().unwrap();
+    pipeline.push(Arc::new(Reverse));
+    let mut output = playlist(&["https://cdn.example/a/chunked/0.ts", "1.ts"]);
+    pipeline
+        .apply(&mut output, &TransformContext::default())
+        .unwrap();
+    assert_eq!(uris(&output), ["1.ts", "0.ts"]);
+    assert_eq!(pipeline.to_string(), "relative,reverse");
+}
+
+#[test]
+fn test_parse_offset() {
+    assert_eq!(parse_offset("90").unwrap(), Duration::from_secs(90));
+    assert_eq!(parse_offset("1h2m3s").unwrap(), Duration::from_secs(3723));
+    assert_eq!(parse_offset("").unwrap(), Duration::ZERO);
+    assert!(parse_offset("1d").is_err());
+}
+
+#[test]
+fn test_program_date_time_across_filtered_segments() {
+    let mut original = playlist(&["0.ts", "1-unmuted.ts", "2.ts", "3.ts"]);
+    original.segments[1].duration = 2.5;
+    let video_data = VideoData {
+        streamer_name: Arc::new("streamer".to_string()),
+        video_id: Arc::new("123".to_string()),
+        unix_time_seconds: start_time(),
+    };
+    let dwp = DomainWithPath {
+        domain: "https://cdn.example/".to_string(),
+        path: Arc::new(crate::VideoPath {
+            url_path: video_data.get_url_path(true),
+            video_data: Arc::new(video_data),
+        }),
+    };
+    let context = TransformContext::new(&original, &dwp)
+        .with_valid_segments(&playlist(&["0.ts", "2.ts", "3.ts"]));
+    let pipeline = TransformPipeline::default().with_program_date_time();
+    assert_eq!(
+        pipeline.to_string(),
+        "mute,explicit,filter,discontinuities,program-date-time"
+    );
+    assert_eq!(
+        pipeline.clone().with_program_date_time().to_string(),
+        pipeline.to_string()
+    );
+    let mut output = original.clone();
+    pipeline.apply(&mut output, &context).unwrap();
+    let stamps = output
+        .segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.discontinuity,
+                segment.program_date_time.unwrap().to_rfc3339(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stamps,
+        [
+            (false, "2022-09-24T17:02:09+00:00".to_string()),
+            (true, "2022-09-24T17:02:21.500+00:00".to_string()),
+            (false, "2022-09-24T17:02:31.500+00:00".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_unmute_only_uses_valid_unmuted_segments() {
+    let original = playlist(&["0.ts", "1-unmuted.ts", "2-unmuted.ts"]);
+    let checked =
+        context(&original).with_unmuted_segments(["https://cdn.example/path/chunked/1-unmuted.ts"]);
+    let mut output = original.clone();
+    "mute,unmute"
+        .parse::<TransformPipeline>()
+        .unwrap()
+        .apply(&mut output, &checked)
+        .unwrap();
+    assert_eq!(uris(&output), ["0.ts", "1-unmuted.ts", "2-muted.ts"]);
+
+    // without checks, the muted versions are kept
+    let mut output = original.clone();
+    "unmute"
+        .parse::<TransformPipeline>()
+        .unwrap()
+        .apply(&mut output, &context(&original))
+        .unwrap();
+    assert_eq!(uris(&output), ["0.ts", "1-muted.ts", "2-muted.ts"]);
+}
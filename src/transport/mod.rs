@@ -0,0 +1,205 @@
+mod tests;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH},
+    Client, Version,
+};
+
+/// Sends the GET requests of an `HttpClient`.
+/// `reqwest::Client` is the default; other implementations can wrap it or fake a CDN entirely.
+///
+/// Implementations should fail with `ConnectionFailed` (or a `reqwest::Error`) when the server
+/// couldn't be reached, so that the watchdog and the concurrency controller can react.
+pub trait Transport: Send + Sync {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>>;
+}
+
+/// The status and headers of a response. The body is only read when `bytes` is called.
+pub struct TransportResponse {
+    url: String,
+    status: u16,
+    version: Version,
+    headers: HeaderMap,
+    content_length: Option<u64>,
+    body: BoxFuture<'static, anyhow::Result<Bytes>>,
+}
+
+/// The server could not be reached, e.g. the connection was refused or timed out.
+#[derive(Debug)]
+pub struct ConnectionFailed(pub String);
+
+impl Display for ConnectionFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "connection failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConnectionFailed {}
+
+/// Whether `err` means that the request never got a response.
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<reqwest::Error>() {
+        Some(err) => err.is_connect() || err.is_timeout() || err.is_request(),
+        None => err.is::<ConnectionFailed>(),
+    }
+}
+
+impl TransportResponse {
+    /// A response whose body is already in memory.
+    pub fn new(url: &str, status: u16, mut headers: HeaderMap, body: Bytes) -> Self {
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        Self {
+            url: url.to_string(),
+            status,
+            version: Version::HTTP_11,
+            content_length: Some(body.len() as u64),
+            headers,
+            body: Box::pin(async move { Ok(body) }),
+        }
+    }
+
+    /// A response whose body is read by `body` once it is needed.
+    pub fn streaming(
+        url: &str,
+        status: u16,
+        headers: HeaderMap,
+        content_length: Option<u64>,
+        body: BoxFuture<'static, anyhow::Result<Bytes>>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            status,
+            version: Version::HTTP_11,
+            headers,
+            content_length,
+            body,
+        }
+    }
+
+    /// Sets the HTTP version that the response came over, which is HTTP/1.1 by default.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The url of the response, which differs from the requested url after a redirect.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    pub async fn bytes(self) -> anyhow::Result<Bytes> {
+        self.body.await
+    }
+}
+
+impl std::fmt::Debug for TransportResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportResponse")
+            .field("url", &self.url)
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transport for Client {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        Box::pin(async move {
+            let response = Client::get(self, url).send().await?;
+            let url = response.url().to_string();
+            let status = response.status().as_u16();
+            let version = response.version();
+            let headers = response.headers().clone();
+            let content_length = response.content_length();
+            Ok(TransportResponse::streaming(
+                &url,
+                status,
+                headers,
+                content_length,
+                Box::pin(async move { Ok(response.bytes().await?) }),
+            )
+            .with_version(version))
+        })
+    }
+}
+
+/// An in-memory CDN for deterministic tests.
+/// Urls that weren't added answer with a 404, and every requested url is remembered.
+#[derive(Default)]
+pub struct FakeCdn {
+    responses: Mutex<HashMap<String, FakeResponse>>,
+    requests: Mutex<Vec<String>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum FakeResponse {
+    Status(u16, Bytes),
+    ConnectionFailed,
+}
+
+impl FakeCdn {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn insert(&self, url: impl Into<String>, response: FakeResponse) {
+        self.responses.lock().unwrap().insert(url.into(), response);
+    }
+
+    /// Serves `body` with a 200 at `url`.
+    pub fn serve(&self, url: impl Into<String>, body: impl Into<Bytes>) {
+        self.insert(url, FakeResponse::Status(200, body.into()));
+    }
+
+    /// Every url requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for FakeCdn {
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, anyhow::Result<TransportResponse>> {
+        self.requests.lock().unwrap().push(url.to_string());
+        let response = self.responses.lock().unwrap().get(url).cloned();
+        Box::pin(async move {
+            match response {
+                Some(FakeResponse::Status(status, body)) => {
+                    Ok(TransportResponse::new(url, status, HeaderMap::new(), body))
+                }
+                Some(FakeResponse::ConnectionFailed) => {
+                    Err(ConnectionFailed(url.to_string()).into())
+                }
+                None => Ok(TransportResponse::new(
+                    url,
+                    404,
+                    HeaderMap::new(),
+                    Bytes::new(),
+                )),
+            }
+        })
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+use super::*;
+#[cfg(test)]
+use crate::{
+    get_valid_dwp, url_path_to_video_data, HeadStart, HttpClient, RateLimitConfig, RequestOutcome,
+};
+
+#[tokio::test]
+async fn test_search_against_fake_cdn() {
+    let video_data = url_path_to_video_data("abc_streamer_123_1664038929").unwrap();
+    let cdn = FakeCdn::new();
+    // the VOD is on the second domain, under the path that uses the second of the start time
+    let url_path = video_data.with_offset(1).get_url_path(false);
+    cdn.serve(
+        format!("https://b.invalid/{}/chunked/index-dvr.m3u8", url_path),
+        "#EXTM3U\n",
+    );
+    let client = HttpClient::with_transport(cdn.clone(), &RateLimitConfig::default());
+    let domains = ["https://a.invalid/", "https://b.invalid/"].map(|d| Arc::new(d.to_string()));
+    let found = get_valid_dwp(
+        &domains,
+        2,
+        video_data.with_offset(0),
+        client,
+        0,
+        HeadStart::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(found.dwp.domain.as_str(), "https://b.invalid/");
+    assert_eq!(found.dwp.path.url_path, url_path);
+    assert_eq!(found.body, "#EXTM3U\n");
+    // every unix time path is tried before the paths with seconds
+    let requests = cdn.requests();
+    assert!(requests[..4].iter().all(|url| url.contains("_16640389")));
+    assert!(requests[4..].iter().all(|url| !url.contains("_16640389")));
+}
+
+#[tokio::test]
+async fn test_fake_connection_failure() {
+    let cdn = FakeCdn::new();
+    cdn.insert("https://a.invalid/x", FakeResponse::ConnectionFailed);
+    let client = HttpClient::with_transport(cdn, &RateLimitConfig::default());
+    let err = client.get("https://a.invalid/x").await.unwrap_err();
+    assert!(is_connection_error(&err));
+    assert_eq!(
+        RequestOutcome::from_anyhow_error(&err),
+        RequestOutcome::Overloaded
+    );
+    let observations = client.host_observations();
+    assert_eq!(observations["https://a.invalid/"].connection_failures, 1);
+    let response = client.get("https://a.invalid/y").await.unwrap();
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,102 @@
+mod tests;
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug)]
+pub struct WatchdogConfig {
+    /// Rebuild the client if requests are in flight but none has completed for this long.
+    pub stall_timeout: Duration,
+    /// Rebuild the client after this many connection errors or timeouts in a row.
+    pub max_consecutive_connection_errors: u32,
+    /// Build every replacement client in HTTP/1.1-only mode.
+    pub fall_back_to_http1: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_timeout: Duration::from_secs(10),
+            max_consecutive_connection_errors: 20,
+            fall_back_to_http1: false,
+        }
+    }
+}
+
+/// Tracks request completions so that a hung connection can be detected.
+pub struct Watchdog {
+    pub config: WatchdogConfig,
+    last_completion: Mutex<Instant>,
+    in_flight: AtomicUsize,
+    consecutive_connection_errors: AtomicU32,
+}
+
+/// Counts one request as in flight until it is finished or dropped, so that requests that are
+/// cancelled or lose a race don't keep the stall timer running.
+pub struct RequestGuard<'a> {
+    watchdog: &'a Watchdog,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            last_completion: Mutex::new(Instant::now()),
+            in_flight: AtomicUsize::new(0),
+            consecutive_connection_errors: AtomicU32::new(0),
+        }
+    }
+
+    pub fn request_started(&self) -> RequestGuard<'_> {
+        // the stall timer only runs while there is something to wait for
+        if self.in_flight.fetch_add(1, Ordering::Relaxed) == 0 {
+            *self.last_completion.lock().unwrap() = Instant::now();
+        }
+        RequestGuard { watchdog: self }
+    }
+
+    fn request_finished(&self, is_connection_error: bool) -> bool {
+        if is_connection_error {
+            let errors = self
+                .consecutive_connection_errors
+                .fetch_add(1, Ordering::Relaxed)
+                + 1;
+            errors >= self.config.max_consecutive_connection_errors
+        } else {
+            *self.last_completion.lock().unwrap() = Instant::now();
+            self.consecutive_connection_errors
+                .store(0, Ordering::Relaxed);
+            false
+        }
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) > 0
+            && self.last_completion.lock().unwrap().elapsed() >= self.config.stall_timeout
+    }
+
+    /// Starts the counters over for a freshly built client.
+    pub fn reset(&self) {
+        *self.last_completion.lock().unwrap() = Instant::now();
+        self.consecutive_connection_errors
+            .store(0, Ordering::Relaxed);
+    }
+}
+
+impl RequestGuard<'_> {
+    /// Returns `true` if the connection errors have piled up enough to warrant a new client.
+    pub fn finish(self, is_connection_error: bool) -> bool {
+        self.watchdog.request_finished(is_connection_error)
+    }
+}
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        self.watchdog.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_watchdog_connection_errors() {
+    let watchdog = Watchdog::new(WatchdogConfig {
+        max_consecutive_connection_errors: 2,
+        ..WatchdogConfig::default()
+    });
+    assert!(!watchdog.request_started().finish(true));
+    assert!(!watchdog.request_started().finish(false));
+    assert!(!watchdog.request_started().finish(true));
+    assert!(watchdog.request_started().finish(true));
+    watchdog.reset();
+    assert!(!watchdog.request_started().finish(true));
+}
+
+#[test]
+fn test_watchdog_stall() {
+    let watchdog = Watchdog::new(WatchdogConfig {
+        stall_timeout: Duration::ZERO,
+        ..WatchdogConfig::default()
+    });
+    assert!(!watchdog.is_stalled());
+    let request = watchdog.request_started();
+    assert!(watchdog.is_stalled());
+    request.finish(false);
+    assert!(!watchdog.is_stalled());
+}
+
+#[test]
+fn test_watchdog_dropped_request_is_not_in_flight() {
+    let watchdog = Watchdog::new(WatchdogConfig {
+        stall_timeout: Duration::ZERO,
+        ..WatchdogConfig::default()
+    });
+    let request = watchdog.request_started();
+    assert!(watchdog.is_stalled());
+    drop(request);
+    assert!(!watchdog.is_stalled());
+}